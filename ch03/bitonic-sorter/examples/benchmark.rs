@@ -1,5 +1,3 @@
-use num_cpus;
-
 use bitonic_sorter::SortOrder;
 // 第3段階のsort関数をseq_sortという別名で使用する
 use bitonic_sorter::third::sort as seq_sort;
@@ -24,7 +22,7 @@ fn main() {
         // ステータスコード1で終了する
         eprintln!(
             "Usage {} <number of elements in bits>",
-            env::args().nth(0).unwrap()
+            env::args().next().unwrap()
         );
         std::process::exit(1);
     }
@@ -0,0 +1,55 @@
+use bitonic_sorter::cache::MemoizingComparator;
+use bitonic_sorter::parallel::sort_refs_by;
+use bitonic_sorter::utils::new_u32_vec;
+
+use std::cmp::Ordering;
+use std::env;
+use std::str::FromStr;
+use std::time::Instant;
+
+// 比較に時間がかかる要素（u32を4096個並べた配列）への参照をソートし、
+// MemoizingComparatorで比較の結果を覚えたときと、覚えないときの時間を比べる
+// あわせて、バイトニックソートが同じ2つの要素を何回比べ直したか（ヒット数）を表示する
+fn main() {
+    let bits = match env::args().nth(1) {
+        Some(n) => u32::from_str(&n).expect("error parsing argument"),
+        None => {
+            eprintln!(
+                "Usage {} <number of elements in bits>",
+                env::args().next().unwrap()
+            );
+            std::process::exit(1);
+        }
+    };
+    let len = 1 << bits;
+    // 先頭の大部分が等しいので、比較のたびに配列のほとんどを読むことになる
+    let values: Vec<Vec<u32>> = new_u32_vec(len)
+        .into_iter()
+        .map(|v| {
+            let mut value = vec![0; 4096];
+            value[4095] = v;
+            value
+        })
+        .collect();
+    println!("sorting {} references to 16KiB values", len);
+
+    let mut refs: Vec<&Vec<u32>> = values.iter().collect();
+    let start = Instant::now();
+    sort_refs_by(&mut refs, &expensive_cmp).expect("failed to sort");
+    println!("  plain:     {:?}", start.elapsed());
+
+    let mut refs: Vec<&Vec<u32>> = values.iter().collect();
+    let memo = MemoizingComparator::new(expensive_cmp);
+    let start = Instant::now();
+    sort_refs_by(&mut refs, &|a, b| memo.compare(a, b)).expect("failed to sort");
+    println!(
+        "  memoizing: {:?} ({} comparisons, {} hits)",
+        start.elapsed(),
+        memo.misses(),
+        memo.hits()
+    );
+}
+
+fn expensive_cmp(a: &Vec<u32>, b: &Vec<u32>) -> Ordering {
+    a.cmp(b)
+}
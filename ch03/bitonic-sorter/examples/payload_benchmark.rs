@@ -0,0 +1,64 @@
+use bitonic_sorter::parallel::{parallel_threshold, sort as par_sort};
+use bitonic_sorter::third::sort as seq_sort;
+use bitonic_sorter::utils::{is_sorted_ascending, new_payload_vec, Payload};
+use bitonic_sorter::SortOrder;
+
+use std::env;
+use std::str::FromStr;
+use std::time::{Duration, Instant};
+
+// 要素のサイズ（8、64、256バイト）ごとに順次ソートと並列ソートの速度を比べる
+// 並列化のオーバーヘッドに対して交換のコストがどれくらい効くかを確かめ、
+// parallel::parallel_threshold のしきい値を決める材料にする
+fn main() {
+    let bits = match env::args().nth(1) {
+        Some(n) => u32::from_str(&n).expect("error parsing argument"),
+        None => {
+            eprintln!(
+                "Usage {} <number of elements in bits>",
+                env::args().next().unwrap()
+            );
+            std::process::exit(1);
+        }
+    };
+    let len = 1 << bits;
+
+    println!(
+        "cpu info: {} physical cores, {} logical cores",
+        num_cpus::get_physical(),
+        num_cpus::get()
+    );
+    run_sorts::<8>(len);
+    run_sorts::<64>(len);
+    run_sorts::<256>(len);
+}
+
+fn run_sorts<const N: usize>(len: usize) {
+    println!(
+        "payload {} bytes: sorting {} elements (threshold: {})",
+        N,
+        len,
+        parallel_threshold::<Payload<N>>()
+    );
+
+    let mut x = new_payload_vec::<N>(len);
+    let seq_duration = timed(|| seq_sort(&mut x, &SortOrder::Ascending).expect("Failed to sort: "));
+    assert!(is_sorted_ascending(&x));
+
+    let mut x = new_payload_vec::<N>(len);
+    let par_duration = timed(|| par_sort(&mut x, &SortOrder::Ascending).expect("Failed to sort: "));
+    assert!(is_sorted_ascending(&x));
+
+    println!(
+        "  seq_sort: {:?}, par_sort: {:?}, speed up: {:.2}x",
+        seq_duration,
+        par_duration,
+        seq_duration.as_secs_f64() / par_duration.as_secs_f64()
+    );
+}
+
+fn timed<F: FnOnce()>(f: F) -> Duration {
+    let start = Instant::now();
+    f();
+    start.elapsed()
+}
@@ -0,0 +1,36 @@
+use bitonic_sorter::parallel::sort_by_counting;
+use bitonic_sorter::utils::{is_sorted_ascending, new_u32_vec};
+use bitonic_sorter::SortMetrics;
+
+use std::env;
+use std::str::FromStr;
+use std::time::Instant;
+
+// 逆順に並んだ入力が、ネットワークを実行せずに反転だけ（O(n)）でソートされることを
+// ランダムな入力のソート（O(n log^2 n)）と比べて確かめる
+// 既定の要素数は2^23（約800万）
+fn main() {
+    let bits = match env::args().nth(1) {
+        Some(n) => u32::from_str(&n).expect("error parsing argument"),
+        None => 23,
+    };
+    let len = 1 << bits;
+    println!("sorting {} integers", len);
+
+    let mut x: Vec<u32> = (0..len as u32).rev().collect();
+    timed(&mut x, "reversed");
+    let mut x = new_u32_vec(len);
+    timed(&mut x, "random");
+}
+
+fn timed(x: &mut [u32], name: &str) -> SortMetrics {
+    let start = Instant::now();
+    let metrics = sort_by_counting(x, &|a, b| a.cmp(b)).expect("Failed to sort: ");
+    let dur = start.elapsed();
+    assert!(is_sorted_ascending(x));
+    println!(
+        "  {}: {:?} ({} comparisons, {} swaps)",
+        name, dur, metrics.comparisons, metrics.swaps
+    );
+    metrics
+}
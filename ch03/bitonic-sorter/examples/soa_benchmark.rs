@@ -0,0 +1,46 @@
+use bitonic_sorter::parallel::{sort_paired_by, sort_soa_by};
+use bitonic_sorter::utils::{is_sorted_ascending, new_payload_vec, new_u32_vec, Payload};
+
+use std::env;
+use std::str::FromStr;
+use std::time::{Duration, Instant};
+
+// キーの配列と大きなペイロード（256バイト）の配列を一緒にソートし、
+// ペイロードを交換で並べ替えるsort_paired_byと、移動で並べ替えるsort_soa_byを比べる
+fn main() {
+    let bits = match env::args().nth(1) {
+        Some(n) => u32::from_str(&n).expect("error parsing argument"),
+        None => {
+            eprintln!(
+                "Usage {} <number of elements in bits>",
+                env::args().next().unwrap()
+            );
+            std::process::exit(1);
+        }
+    };
+    let len = 1 << bits;
+    let keys = new_u32_vec(len);
+    let payloads = new_payload_vec::<256>(len);
+    println!("sorting {} keys with 256-byte payloads", len);
+
+    let dur = timed(&keys, &payloads, |k, p| {
+        sort_paired_by(k, p, &|a, b| a.cmp(b))
+    });
+    println!("  sort_paired_by: {:?}", dur);
+    let dur = timed(&keys, &payloads, |k, p| sort_soa_by(k, p, &|a, b| a.cmp(b)));
+    println!("  sort_soa_by:    {:?}", dur);
+}
+
+fn timed<F, E>(keys: &[u32], payloads: &[Payload<256>], sorter: F) -> Duration
+where
+    F: Fn(&mut [u32], &mut [Payload<256>]) -> Result<(), E>,
+    E: std::fmt::Debug,
+{
+    let mut k = keys.to_vec();
+    let mut p = payloads.to_vec();
+    let start = Instant::now();
+    sorter(&mut k, &mut p).expect("Failed to sort: ");
+    let dur = start.elapsed();
+    assert!(is_sorted_ascending(&k));
+    dur
+}
@@ -0,0 +1,54 @@
+use bitonic_sorter::io::{read_u32, write_u32, Format};
+use bitonic_sorter::parallel::sorted_from;
+use bitonic_sorter::SortOrder;
+
+use std::env;
+use std::fs::File;
+use std::process;
+
+// u32の値が並んだファイルを読み込んでソートし、別のファイルに書き出す
+// 形式は--formatで指定する（text：1行に1つの10進数、binary-le/binary-be：4バイトずつの
+// リトルエンディアン/ビッグエンディアン）。入力と出力は同じ形式になる
+// 要素数は2のべき乗でなくてもよい
+fn main() {
+    let mut format = Format::Text;
+    let mut order = SortOrder::Ascending;
+    let mut paths = Vec::new();
+
+    let mut args = env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--format" => {
+                let value = args.next().unwrap_or_else(|| usage());
+                format = value.parse().unwrap_or_else(|err| {
+                    eprintln!("{}", err);
+                    usage()
+                });
+            }
+            "--descending" => order = SortOrder::Descending,
+            _ => paths.push(arg),
+        }
+    }
+    if paths.len() != 2 {
+        usage();
+    }
+
+    let input = File::open(&paths[0]).unwrap_or_else(|err| fail(&paths[0], err));
+    let values = read_u32(input, format).unwrap_or_else(|err| fail(&paths[0], err));
+    let sorted = sorted_from(values, &order);
+    let output = File::create(&paths[1]).unwrap_or_else(|err| fail(&paths[1], err));
+    write_u32(output, &sorted, format).unwrap_or_else(|err| fail(&paths[1], err));
+}
+
+fn usage() -> ! {
+    eprintln!(
+        "Usage {} [--format text|binary-le|binary-be] [--descending] <input> <output>",
+        env::args().next().unwrap()
+    );
+    process::exit(1);
+}
+
+fn fail(path: &str, err: std::io::Error) -> ! {
+    eprintln!("{}: {}", path, err);
+    process::exit(1);
+}
@@ -0,0 +1,48 @@
+use bitonic_sorter::parallel::{parallel_threshold, sort_by_with_threshold};
+use bitonic_sorter::utils::{is_sorted_ascending, new_u32_vec};
+
+use std::env;
+use std::str::FromStr;
+use std::time::Instant;
+
+// 並列に処理するかを決めるしきい値を変えながら同じ入力をソートし、スループットを比べる
+// PARALLEL_THRESHOLDの既定値を、推測ではなく計測した結果から選ぶために使う
+fn main() {
+    let bits = match env::args().nth(1) {
+        Some(n) => u32::from_str(&n).expect("error parsing argument"),
+        None => {
+            eprintln!(
+                "Usage {} <number of elements in bits>",
+                env::args().next().unwrap()
+            );
+            std::process::exit(1);
+        }
+    };
+    let len = 1 << bits;
+    let input = new_u32_vec(len);
+
+    println!(
+        "cpu info: {} physical cores, {} logical cores",
+        num_cpus::get_physical(),
+        num_cpus::get()
+    );
+    println!(
+        "sorting {} integers (current threshold: {})",
+        len,
+        parallel_threshold::<u32>()
+    );
+
+    for &threshold in &[256, 1024, 4096, 16384, 65536] {
+        let mut x = input.clone();
+        let start = Instant::now();
+        sort_by_with_threshold(&mut x, &|a, b| a.cmp(b), threshold).expect("Failed to sort: ");
+        let dur = start.elapsed();
+        assert!(is_sorted_ascending(&x));
+        println!(
+            "  threshold {:>5}: {:?} ({:.1} M elements/s)",
+            threshold,
+            dur,
+            len as f64 / dur.as_secs_f64() / 1e6
+        );
+    }
+}
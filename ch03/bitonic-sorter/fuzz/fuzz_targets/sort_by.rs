@@ -0,0 +1,38 @@
+// 任意のバイト列をu32の配列として解釈し、parallel::sortに与えるファジングターゲット
+// 実行するにはcargo-fuzzとnightlyのツールチェインが必要：
+//   cd fuzz && cargo +nightly fuzz run sort_by
+#![no_main]
+
+use bitonic_sorter::parallel::sort;
+use bitonic_sorter::SortOrder;
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|data: &[u8]| {
+    // 先頭の1バイトでソート順を選び、残りを4バイトずつu32に変換する
+    let (order, bytes) = match data.split_first() {
+        Some((&first, rest)) if first % 2 == 0 => (SortOrder::Ascending, rest),
+        Some((_, rest)) => (SortOrder::Descending, rest),
+        None => return,
+    };
+    let mut values: Vec<u32> = bytes
+        .chunks_exact(4)
+        .map(|b| u32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+        .collect();
+
+    // 要素数を2のべき乗に切り詰める（0要素のときはエラーになることを確かめる）
+    if values.is_empty() {
+        assert!(sort(&mut values, &order).is_err());
+        return;
+    }
+    values.truncate(1 << (usize::BITS - 1 - values.len().leading_zeros()));
+
+    let mut expected = values.clone();
+    expected.sort_unstable();
+    if let SortOrder::Descending = order {
+        expected.reverse();
+    }
+
+    assert!(sort(&mut values, &order).is_ok());
+    // 標準ライブラリのソートと一致すれば、ソート済みかつ入力の並べ替えになっている
+    assert_eq!(values, expected);
+});
@@ -0,0 +1,124 @@
+use super::parallel::compare_and_swap_at;
+use super::utils::log2_exact;
+use rayon::prelude::*;
+
+// 0-1原理で検査できる要素数の上限（2^n通りの入力をすべて試すため）
+pub const MAX_VERIFIABLE_LEN: usize = 24;
+
+// 比較の列comparisonsが、要素数nのソーティングネットワークになっているかを調べる
+// 各(i, j)は「位置iとjを比較し、小さい方をiに置く」ことを表す
+// 0-1原理により、0と1だけからなる2^n通りの入力をすべてソートできれば
+// 任意の入力をソートできる。入力は並列に検査する
+// nがMAX_VERIFIABLE_LENを超えるときはパニックする。範囲外の位置を含む列は不正とみなす
+pub fn is_sorting_network(comparisons: &[(usize, usize)], n: usize) -> bool {
+    assert!(
+        n <= MAX_VERIFIABLE_LEN,
+        "n must be at most {} to try all 2^n inputs (n: {})",
+        MAX_VERIFIABLE_LEN,
+        n
+    );
+    if comparisons.iter().any(|&(i, j)| i >= n || j >= n) {
+        return false;
+    }
+
+    (0..1u32 << n).into_par_iter().all(|bits| {
+        let mut input: Vec<u8> = (0..n).map(|k| (bits >> k & 1) as u8).collect();
+        for &(i, j) in comparisons {
+            compare_and_swap_at(&mut input, i, j, &|a: &u8, b: &u8| a.cmp(b));
+        }
+        input.windows(2).all(|w| w[0] <= w[1])
+    })
+}
+
+// 要素数nのバイトニックソーターが行う比較（compare-and-swap）の回数を返す
+// n = 2^kのとき、長さ2^iのバイトニック列のマージはi段で、各段n / 2回の比較を行う
+// これをi = 1..=kについて足すと(n / 2) * k * (k + 1) / 2 = (n / 4) * k * (k + 1)回になる
+// nが2のべき乗でなければパニックする
+pub fn bitonic_comparison_count(n: usize) -> u64 {
+    let k = log2_exact(n).expect("n must be a power of two") as u64;
+    n as u64 * k * (k + 1) / 4
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{bitonic_comparison_count, is_sorting_network};
+    use crate::parallel::sort_by_counting;
+
+    // 要素数4の最適なネットワーク（比較5回）
+    const NETWORK_4: [(usize, usize); 5] = [(0, 1), (2, 3), (0, 2), (1, 3), (1, 2)];
+
+    #[test]
+    fn known_networks_pass() {
+        assert!(is_sorting_network(&NETWORK_4, 4));
+        assert!(is_sorting_network(&[(0, 1)], 2));
+        assert!(is_sorting_network(&[], 1));
+        assert!(is_sorting_network(&[], 0));
+
+        // 要素数8のバイトニックソーター（比較24回）を昇順の比較だけで組んだもの
+        let mut network = Vec::new();
+        let mut k = 2;
+        while k <= 8 {
+            let mut j = k / 2;
+            while j > 0 {
+                for i in 0..8 {
+                    let l = i ^ j;
+                    if l > i {
+                        if i & k == 0 {
+                            network.push((i, l));
+                        } else {
+                            network.push((l, i));
+                        }
+                    }
+                }
+                j /= 2;
+            }
+            k *= 2;
+        }
+        assert_eq!(network.len(), 24);
+        assert!(is_sorting_network(&network, 8));
+    }
+
+    #[test]
+    fn broken_networks_fail() {
+        // 最後の比較が欠けている
+        assert!(!is_sorting_network(&NETWORK_4[..4], 4));
+        // 比較の向きが逆
+        assert!(!is_sorting_network(&[(1, 0)], 2));
+        // 範囲外の位置を含む
+        assert!(!is_sorting_network(&[(0, 4)], 4));
+        // 比較がまったくない
+        assert!(!is_sorting_network(&[], 2));
+    }
+
+    #[test]
+    #[should_panic]
+    fn too_long_to_verify() {
+        is_sorting_network(&[], 25);
+    }
+
+    #[test]
+    fn comparison_count_formula() {
+        assert_eq!(bitonic_comparison_count(1), 0);
+        assert_eq!(bitonic_comparison_count(2), 1);
+        assert_eq!(bitonic_comparison_count(4), 6);
+        assert_eq!(bitonic_comparison_count(8), 24);
+        assert_eq!(bitonic_comparison_count(1 << 20), (1 << 18) * 20 * 21);
+    }
+
+    #[test]
+    fn comparison_count_matches_runtime_counter() {
+        for &n in &[2usize, 4, 8, 16, 32] {
+            // ソート済みでも逆順でもない入力にして、ネットワークを省く確認に掛からないようにする
+            let mut x: Vec<usize> = (0..n).collect();
+            x.swap(0, 1);
+            let metrics = sort_by_counting(&mut x, &|a, b| a.cmp(b)).unwrap();
+            assert_eq!(metrics.comparisons, bitonic_comparison_count(n), "n: {}", n);
+        }
+    }
+
+    #[test]
+    #[should_panic]
+    fn comparison_count_of_non_power_of_two() {
+        bitonic_comparison_count(12);
+    }
+}
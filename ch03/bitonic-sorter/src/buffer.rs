@@ -0,0 +1,140 @@
+use super::parallel::sorted_from;
+use super::SortOrder;
+use std::iter::FromIterator;
+use std::ops::Deref;
+
+// 値を少しずつ追加していき、最後にソート済みのベクタとして取り出すためのバッファ
+// BinaryHeap::into_sorted_vecと同じように、消費するときにまとめてソートする
+#[derive(Debug, Clone, Default)]
+pub struct SortBuffer<T> {
+    items: Vec<T>,
+}
+
+impl<T: Ord + Send> SortBuffer<T> {
+    pub fn new() -> Self {
+        Self { items: Vec::new() }
+    }
+
+    pub fn push(&mut self, value: T) {
+        self.items.push(value);
+    }
+
+    pub fn len(&self) -> usize {
+        self.items.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.items.is_empty()
+    }
+
+    // バッファを消費してソート済みのベクタを返す
+    // 要素数は2のべき乗でなくてもよい（内部で埋めてからソートし、取り除く）
+    pub fn into_sorted_vec(self, order: &SortOrder) -> Vec<T> {
+        sorted_from(self.items, order)
+    }
+}
+
+impl<T> Extend<T> for SortBuffer<T> {
+    fn extend<I: IntoIterator<Item = T>>(&mut self, iter: I) {
+        self.items.extend(iter);
+    }
+}
+
+// 要素が常に昇順に並んでいることを保証するベクタ
+// collectで作ると、集めた要素を最後にまとめてソートする（要素数は2のべき乗でなくてよい）
+// 読み取りはDerefでスライスとして行い、並びを崩すような変更はできない
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SortedVec<T> {
+    items: Vec<T>,
+}
+
+impl<T: Ord> SortedVec<T> {
+    pub fn new() -> Self {
+        Self { items: Vec::new() }
+    }
+
+    // 昇順に並んでいるので二分探索で値を探せる。戻り値はslice::binary_searchと同じ
+    pub fn binary_search(&self, value: &T) -> Result<usize, usize> {
+        self.items.binary_search(value)
+    }
+
+    pub fn into_vec(self) -> Vec<T> {
+        self.items
+    }
+}
+
+impl<T: Ord> Default for SortedVec<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T: Ord + Send> FromIterator<T> for SortedVec<T> {
+    fn from_iter<I: IntoIterator<Item = T>>(iter: I) -> Self {
+        Self {
+            items: sorted_from(iter, &SortOrder::Ascending),
+        }
+    }
+}
+
+impl<T> Deref for SortedVec<T> {
+    type Target = [T];
+
+    fn deref(&self) -> &[T] {
+        &self.items
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{SortBuffer, SortedVec};
+    use crate::utils::{is_sorted_ascending, new_u32_vec};
+    use crate::SortOrder::*;
+
+    #[test]
+    fn build_and_sort_incrementally() {
+        let mut buffer = SortBuffer::new();
+        assert!(buffer.is_empty());
+        buffer.push(30);
+        buffer.push(10);
+        buffer.extend(vec![20, 4, 330]);
+        buffer.push(11);
+        // 要素数6は2のべき乗ではない
+        assert_eq!(buffer.len(), 6);
+        assert_eq!(
+            buffer.clone().into_sorted_vec(&Ascending),
+            vec![4, 10, 11, 20, 30, 330]
+        );
+        assert_eq!(
+            buffer.into_sorted_vec(&Descending),
+            vec![330, 30, 20, 11, 10, 4]
+        );
+    }
+
+    #[test]
+    fn empty_buffer() {
+        let buffer: SortBuffer<u32> = SortBuffer::new();
+        assert_eq!(buffer.into_sorted_vec(&Ascending), Vec::<u32>::new());
+    }
+
+    #[test]
+    fn collect_into_sorted_vec() {
+        let sv: SortedVec<u32> = new_u32_vec(1000).into_iter().map(|v| v % 5000).collect();
+        assert_eq!(sv.len(), 1000);
+        assert!(is_sorted_ascending(&sv));
+
+        let target = sv[421];
+        let found = sv.binary_search(&target).unwrap();
+        assert_eq!(sv[found], target);
+        assert!(sv.binary_search(&5000).is_err());
+
+        let words: SortedVec<&str> = vec!["pear", "fig", "apple"].into_iter().collect();
+        assert_eq!(words.binary_search(&"fig"), Ok(1));
+        assert_eq!(words.binary_search(&"banana"), Err(1));
+        assert_eq!(words.into_vec(), vec!["apple", "fig", "pear"]);
+
+        let empty: SortedVec<u32> = SortedVec::new();
+        assert!(empty.is_empty());
+        assert_eq!(empty.binary_search(&1), Err(0));
+    }
+}
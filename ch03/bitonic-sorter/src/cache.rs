@@ -0,0 +1,300 @@
+use super::parallel::{apply_permutation, sort_by};
+use super::SortError;
+use std::cmp::Ordering;
+use std::collections::hash_map::RandomState;
+use std::collections::HashMap;
+use std::hash::{BuildHasher, Hash};
+use std::marker::PhantomData;
+use std::sync::atomic::{AtomicUsize, Ordering as AtomicOrdering};
+use std::sync::Mutex;
+
+// 入力のハッシュ値をキーに、ソートの結果（並べ替えの順序）を覚えておくソーター
+// 変更されていない大きな配列を何度もソートするときに、2回目以降はソートを省いて
+// 覚えておいた並べ替えを適用するだけで済ませる
+// ハッシュ値が衝突したり、前回と異なるcomparatorが渡されたりしても結果が誤らないように、
+// 並べ替えた後でソート済みかを確認し、そうでなければソートし直す
+pub struct CachingSorter<S = RandomState> {
+    hasher: S,
+    permutations: HashMap<u64, Vec<usize>>,
+    computations: usize,
+}
+
+impl CachingSorter<RandomState> {
+    pub fn new() -> Self {
+        Self::with_hasher(RandomState::new())
+    }
+}
+
+impl Default for CachingSorter<RandomState> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<S: BuildHasher> CachingSorter<S> {
+    // 入力のハッシュ値の計算に使うハッシャーを指定して作る
+    pub fn with_hasher(hasher: S) -> Self {
+        Self {
+            hasher,
+            permutations: HashMap::new(),
+            computations: 0,
+        }
+    }
+
+    // parallel::sort_byと同じようにソートする
+    // 同じ内容の配列を前にソートしていれば、そのときの並べ替えを適用する
+    pub fn sort_by<T, F>(&mut self, array: &mut [T], comparator: &F) -> Result<(), SortError>
+    where
+        T: Hash + Send + Sync,
+        F: Sync + Fn(&T, &T) -> Ordering,
+    {
+        let hash = self.hasher.hash_one(&*array);
+
+        // ソート済みにならなかったときに、すでに適用してしまった並べ替え
+        let mut applied = None;
+        if let Some(permutation) = self.permutations.get(&hash) {
+            if permutation.len() == array.len() {
+                apply_permutation(array, &mut permutation.clone());
+                if array
+                    .windows(2)
+                    .all(|pair| comparator(&pair[0], &pair[1]) != Ordering::Greater)
+                {
+                    return Ok(());
+                }
+                applied = Some(permutation.clone());
+            }
+        }
+
+        // 要素そのものではなく添字をソートして、並べ替えの順序を求める
+        let mut permutation: Vec<usize> = (0..array.len()).collect();
+        {
+            let source = &*array;
+            sort_by(&mut permutation, &|&i: &usize, &j: &usize| {
+                comparator(&source[i], &source[j])
+            })?;
+        }
+        self.computations += 1;
+        // 覚えておく並べ替えは、このハッシュ値を持つ元の入力に対するものにする
+        // 覚えた並べ替えをすでに適用していたなら、それと今求めた並べ替えを合成する
+        let from_input = match applied {
+            Some(applied) => permutation.iter().map(|&i| applied[i]).collect(),
+            None => permutation.clone(),
+        };
+        self.permutations.insert(hash, from_input);
+        apply_permutation(array, &mut permutation);
+        Ok(())
+    }
+
+    // 実際にソートした（キャッシュを使わなかった）回数
+    pub fn computations(&self) -> usize {
+        self.computations
+    }
+
+    // 覚えている並べ替えの数
+    pub fn len(&self) -> usize {
+        self.permutations.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.permutations.is_empty()
+    }
+
+    // 覚えている並べ替えをすべて捨てる
+    pub fn clear(&mut self) {
+        self.permutations.clear();
+    }
+}
+
+// 比較の結果を、比べた2つの要素のアドレスの組をキーに覚えておく比較関数
+// 比較にとても時間がかかる型（大きな構造体を解析して比べるなど）で、同じ2つの要素を
+// 何度も比べる場合に、2回目以降は覚えておいた結果を返す
+// キーはアドレスなので、ソート中に要素そのものが移動すると誤った結果を返す
+// 参照のスライスをソートするparallel::sort_refs_byと組み合わせて、参照先の値が
+// 移動しないようにして使う。ソートが終わったら捨てるか、clearを呼ぶこと
+// バイトニックソートは同じ2つの要素を比べ直すことがあり、1024要素では比較の約13%、
+// 16384要素では約11%が覚えておいた結果で済む（examples/memoizing_benchmark.rsで確認できる）
+// ただし比較のたびにMutexのロックと、ハッシュ表の検索と挿入が入るので、同じベンチマークでは
+// 覚えない比較関数より20〜40%遅くなる。1回の比較が、ロック1回とハッシュ表の操作2回より
+// ずっと時間のかかる場合にだけ使うとよい
+pub struct MemoizingComparator<T, F> {
+    comparator: F,
+    results: Mutex<HashMap<(usize, usize), Ordering>>,
+    hits: AtomicUsize,
+    _marker: PhantomData<fn(&T, &T)>,
+}
+
+impl<T, F> MemoizingComparator<T, F>
+where
+    F: Fn(&T, &T) -> Ordering,
+{
+    pub fn new(comparator: F) -> Self {
+        Self {
+            comparator,
+            results: Mutex::new(HashMap::new()),
+            hits: AtomicUsize::new(0),
+            _marker: PhantomData,
+        }
+    }
+
+    // aとbを比べる。前に同じ2つの要素を（どちらの順でも）比べていれば、その結果を返す
+    pub fn compare(&self, a: &T, b: &T) -> Ordering {
+        let (pa, pb) = (a as *const T as usize, b as *const T as usize);
+        // 比較結果は2つの要素の順序を入れ替えると逆になるので、小さいアドレスを先にして覚える
+        let (key, swapped) = if pa <= pb {
+            ((pa, pb), false)
+        } else {
+            ((pb, pa), true)
+        };
+        let cached = self.results.lock().unwrap().get(&key).copied();
+        let ordering = match cached {
+            Some(ordering) => {
+                self.hits.fetch_add(1, AtomicOrdering::Relaxed);
+                ordering
+            }
+            None => {
+                // ロックを持ったまま比較すると並列に比較できなくなるので、いったん手放す
+                let ordering = if swapped {
+                    (self.comparator)(b, a)
+                } else {
+                    (self.comparator)(a, b)
+                };
+                self.results.lock().unwrap().insert(key, ordering);
+                ordering
+            }
+        };
+        if swapped {
+            ordering.reverse()
+        } else {
+            ordering
+        }
+    }
+
+    // 覚えておいた結果を返した回数
+    pub fn hits(&self) -> usize {
+        self.hits.load(AtomicOrdering::Relaxed)
+    }
+
+    // 実際にcomparatorを呼び出して比べた要素の組の数
+    pub fn misses(&self) -> usize {
+        self.results.lock().unwrap().len()
+    }
+
+    // 覚えている結果をすべて捨てる
+    pub fn clear(&mut self) {
+        self.results.get_mut().unwrap().clear();
+        *self.hits.get_mut() = 0;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{CachingSorter, MemoizingComparator};
+    use crate::parallel::sort_refs_by;
+    use crate::utils::{is_sorted_ascending, is_sorted_descending, new_u32_vec};
+    use std::hash::{BuildHasherDefault, Hasher};
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    #[test]
+    fn second_identical_sort_uses_cache() {
+        let input = new_u32_vec(4096);
+        let mut sorter = CachingSorter::new();
+
+        let mut x = input.clone();
+        assert_eq!(sorter.sort_by(&mut x, &|a, b| a.cmp(b)), Ok(()));
+        assert!(is_sorted_ascending(&x));
+        assert_eq!(sorter.computations(), 1);
+
+        let mut y = input.clone();
+        assert_eq!(sorter.sort_by(&mut y, &|a, b| a.cmp(b)), Ok(()));
+        assert_eq!(y, x);
+        assert_eq!(sorter.computations(), 1);
+        assert_eq!(sorter.len(), 1);
+
+        // 内容が異なればソートし直す
+        let mut z = input;
+        z.swap(0, 1);
+        z[0] ^= 1;
+        assert_eq!(sorter.sort_by(&mut z, &|a, b| a.cmp(b)), Ok(()));
+        assert!(is_sorted_ascending(&z));
+        assert_eq!(sorter.computations(), 2);
+    }
+
+    #[test]
+    fn different_comparator_is_recomputed() {
+        let input = new_u32_vec(1024);
+        let mut sorter = CachingSorter::new();
+        let mut x = input.clone();
+        assert_eq!(sorter.sort_by(&mut x, &|a, b| a.cmp(b)), Ok(()));
+        // 同じ入力でもcomparatorが異なれば、覚えた並べ替えではソート済みにならない
+        let mut y = input;
+        assert_eq!(sorter.sort_by(&mut y, &|a, b| b.cmp(a)), Ok(()));
+        assert!(is_sorted_descending(&y));
+        assert_eq!(sorter.computations(), 2);
+
+        sorter.clear();
+        assert!(sorter.is_empty());
+        assert!(sorter.sort_by(&mut y[..3], &|a, b| a.cmp(b)).is_err());
+    }
+
+    // どんな入力にも同じハッシュ値を返すハッシャー（衝突を起こすため）
+    #[derive(Default)]
+    struct ConstantHasher;
+
+    impl Hasher for ConstantHasher {
+        fn finish(&self) -> u64 {
+            0
+        }
+
+        fn write(&mut self, _bytes: &[u8]) {}
+    }
+
+    #[test]
+    fn hash_collision_is_recomputed_once() {
+        let mut sorter =
+            CachingSorter::with_hasher(BuildHasherDefault::<ConstantHasher>::default());
+        let input = new_u32_vec(1024);
+        let mut x = input.clone();
+        assert_eq!(sorter.sort_by(&mut x, &|a, b| a.cmp(b)), Ok(()));
+        assert_eq!(sorter.computations(), 1);
+
+        // 異なる入力でもハッシュ値が衝突するので、覚えた並べ替えを試してからソートし直す
+        let other: Vec<u32> = new_u32_vec(2048)[1024..].to_vec();
+        let mut expected = other.clone();
+        expected.sort();
+        for _ in 0..3 {
+            let mut y = other.clone();
+            assert_eq!(sorter.sort_by(&mut y, &|a, b| a.cmp(b)), Ok(()));
+            assert_eq!(y, expected);
+        }
+        // ソートし直すのは最初の1回だけで、その後は覚えた並べ替えで済む
+        assert_eq!(sorter.computations(), 2);
+        assert_eq!(sorter.len(), 1);
+    }
+
+    #[test]
+    fn memoizing_comparator_sorts_refs() {
+        let input = new_u32_vec(4096);
+        let calls = AtomicUsize::new(0);
+        let memo = MemoizingComparator::new(|a: &u32, b: &u32| {
+            calls.fetch_add(1, Ordering::Relaxed);
+            a.cmp(b)
+        });
+
+        let mut refs: Vec<&u32> = input.iter().collect();
+        assert_eq!(sort_refs_by(&mut refs, &|a, b| memo.compare(a, b)), Ok(()));
+        assert!(refs.windows(2).all(|w| w[0] <= w[1]));
+        assert_eq!(calls.load(Ordering::Relaxed), memo.misses());
+        let first_hits = memo.hits();
+
+        // 同じ参照を並べ直すと、すべての比較で覚えておいた結果を使う
+        refs.reverse();
+        assert_eq!(sort_refs_by(&mut refs, &|a, b| memo.compare(a, b)), Ok(()));
+        assert!(refs.windows(2).all(|w| w[0] <= w[1]));
+        assert_eq!(calls.load(Ordering::Relaxed), memo.misses());
+        assert!(memo.hits() > first_hits);
+
+        // 順序を入れ替えて比べても、逆の結果を返す
+        assert_eq!(memo.compare(&input[0], &input[1]), input[0].cmp(&input[1]));
+        assert_eq!(memo.compare(&input[1], &input[0]), input[1].cmp(&input[0]));
+    }
+}
@@ -0,0 +1,136 @@
+use super::parallel::sort_by;
+use super::{SortError, SortOrder};
+use icu_collator::options::CollatorOptions;
+use icu_collator::{Collator, CollatorPreferences};
+use icu_locale_core::Locale;
+
+// ICUの照合規則（collation）にしたがって文字列をソートする
+// str::cmpはバイト列として比較するので、アクセント付きの文字などが
+// 言語ごとの辞書順に並ばない
+// localeには"de"（ドイツ語）や"sv"（スウェーデン語）などのロケール識別子を与える
+pub fn sort_collated(array: &mut [&str], locale: &str, order: &SortOrder) -> Result<(), SortError> {
+    let invalid_locale = || SortError::InvalidLocale {
+        locale: locale.to_string(),
+    };
+    let locale = Locale::try_from_str(locale).map_err(|_| invalid_locale())?;
+    let collator = Collator::try_new(
+        CollatorPreferences::from(&locale),
+        CollatorOptions::default(),
+    )
+    .map_err(|_| invalid_locale())?;
+
+    match *order {
+        SortOrder::Ascending => sort_by(array, &|a, b| collator.compare(a, b)),
+        SortOrder::Descending => sort_by(array, &|a, b| collator.compare(b, a)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::sort_collated;
+    use crate::SortError;
+    use crate::SortOrder::*;
+
+    fn words() -> Vec<&'static str> {
+        vec![
+            "zebra",
+            "äpfel",
+            "apfel",
+            "Österreich",
+            "ost",
+            "Zürich",
+            "bär",
+            "baum",
+        ]
+    }
+
+    #[test]
+    fn sort_german() {
+        // ドイツ語ではäはaと同じ位置に並ぶ
+        let mut x = words();
+        assert_eq!(sort_collated(&mut x, "de", &Ascending), Ok(()));
+        assert_eq!(
+            x,
+            vec![
+                "apfel",
+                "äpfel",
+                "bär",
+                "baum",
+                "ost",
+                "Österreich",
+                "zebra",
+                "Zürich"
+            ]
+        );
+    }
+
+    #[test]
+    fn sort_swedish() {
+        // スウェーデン語ではäやöはzより後ろに並ぶ
+        let mut x = words();
+        assert_eq!(sort_collated(&mut x, "sv", &Ascending), Ok(()));
+        assert_eq!(
+            x,
+            vec![
+                "apfel",
+                "baum",
+                "bär",
+                "ost",
+                "zebra",
+                "Zürich",
+                "äpfel",
+                "Österreich"
+            ]
+        );
+    }
+
+    #[test]
+    fn sort_swedish_descending() {
+        let mut x = words();
+        assert_eq!(sort_collated(&mut x, "sv", &Descending), Ok(()));
+        assert_eq!(
+            x,
+            vec![
+                "Österreich",
+                "äpfel",
+                "Zürich",
+                "zebra",
+                "ost",
+                "bär",
+                "baum",
+                "apfel"
+            ]
+        );
+    }
+
+    #[test]
+    fn differs_from_str_cmp() {
+        // str::cmpでは大文字が小文字より前に、äやöはすべての英字より後ろに並ぶ
+        let mut x = words();
+        x.sort();
+        assert_eq!(
+            x,
+            vec![
+                "Zürich",
+                "apfel",
+                "baum",
+                "bär",
+                "ost",
+                "zebra",
+                "Österreich",
+                "äpfel"
+            ]
+        );
+    }
+
+    #[test]
+    fn invalid_locale() {
+        let mut x = words();
+        assert_eq!(
+            sort_collated(&mut x, "not a locale!", &Ascending),
+            Err(SortError::InvalidLocale {
+                locale: "not a locale!".to_string()
+            })
+        );
+    }
+}
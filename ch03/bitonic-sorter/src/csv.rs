@@ -0,0 +1,93 @@
+use super::parallel::sort_padded_by;
+use super::SortOrder;
+use ::csv::{Reader, Writer};
+use std::cmp::Ordering;
+use std::io;
+use std::path::Path;
+
+// inputのCSVファイルの行を、column番目（0から数える）の列の値でソートしてoutputに書き出す
+// 1行目は見出しの行として扱い、ソートせずにそのまま先頭に書き出す
+// numericがtrueなら列の値を浮動小数点数として比較し（f64::total_cmp）、
+// falseなら文字列として（バイト列の辞書順で）比較する
+// 値が等しい行は、入力での順序を保つ
+// 行数は2のべき乗でなくてもよい（内部で番兵の行で埋めてからソートし、取り除く）
+// 列の数が足りない行や、numericがtrueで数として読めない値があれば、InvalidDataエラーを返す
+pub fn sort_csv_column(
+    input: &Path,
+    output: &Path,
+    column: usize,
+    order: &SortOrder,
+    numeric: bool,
+) -> io::Result<()> {
+    let mut reader = Reader::from_path(input)?;
+    let headers = reader.byte_headers()?.clone();
+    let records = reader.byte_records().collect::<Result<Vec<_>, _>>()?;
+
+    let fields = records
+        .iter()
+        .enumerate()
+        .map(|(i, record)| {
+            record.get(column).ok_or_else(|| {
+                invalid_data(format!(
+                    "{}: row {} has no column {}",
+                    input.display(),
+                    i + 1,
+                    column
+                ))
+            })
+        })
+        .collect::<io::Result<Vec<&[u8]>>>()?;
+    let permutation = if numeric {
+        let keys = fields
+            .iter()
+            .enumerate()
+            .map(|(i, field)| {
+                parse_number(field).ok_or_else(|| {
+                    invalid_data(format!(
+                        "{}: row {} column {} is not a number: {:?}",
+                        input.display(),
+                        i + 1,
+                        column,
+                        String::from_utf8_lossy(field)
+                    ))
+                })
+            })
+            .collect::<io::Result<Vec<f64>>>()?;
+        sorted_positions(keys, order, f64::total_cmp)
+    } else {
+        sorted_positions(fields, order, |a: &&[u8], b: &&[u8]| a.cmp(b))
+    };
+
+    let mut writer = Writer::from_path(output)?;
+    writer.write_byte_record(&headers)?;
+    for i in permutation {
+        writer.write_byte_record(&records[i])?;
+    }
+    writer.flush()
+}
+
+// キーをorderの順に並べたときの、元の位置の列を返す
+// キーが等しいときは元の位置で比べるので、入力の順序が保たれる
+fn sorted_positions<K, F>(keys: Vec<K>, order: &SortOrder, compare: F) -> Vec<usize>
+where
+    K: Send,
+    F: Sync + Fn(&K, &K) -> Ordering,
+{
+    let keyed: Vec<(K, usize)> = keys.into_iter().zip(0..).collect();
+    let sorted = sort_padded_by(keyed, &|a: &(K, usize), b: &(K, usize)| {
+        let ordering = match *order {
+            SortOrder::Ascending => compare(&a.0, &b.0),
+            SortOrder::Descending => compare(&b.0, &a.0),
+        };
+        ordering.then(a.1.cmp(&b.1))
+    });
+    sorted.into_iter().map(|(_, i)| i).collect()
+}
+
+fn parse_number(field: &[u8]) -> Option<f64> {
+    std::str::from_utf8(field).ok()?.trim().parse().ok()
+}
+
+fn invalid_data(message: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
@@ -0,0 +1,206 @@
+use super::parallel::sort_by;
+use super::{SortError, SortOrder};
+use std::cmp::Ordering;
+
+// f32とf64はNaNがあるのでOrdを実装しておらず、そのままではsortに渡せない
+// このモジュールの関数はtotal_cmp（IEEE 754のtotalOrder）で比較するので、
+// NaNを含んでいても結果が一意に決まる
+// total_cmpでは -NaN < -∞ < 負の数 < -0.0 < +0.0 < 正の数 < +∞ < +NaN の順になる
+
+// NaNをソート結果のどちら側に集めるか
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NanPlacement {
+    First, // 先頭に集める
+    Last,  // 末尾に集める
+}
+
+// total_cmpの順（降順ならその逆順）でソートする
+// 符号ビットの立ったNaNは昇順の先頭に、それ以外のNaNは末尾に来る
+pub fn sort_f32_total(array: &mut [f32], order: &SortOrder) -> Result<(), SortError> {
+    match *order {
+        SortOrder::Ascending => sort_by(array, &|a: &f32, b: &f32| a.total_cmp(b)),
+        SortOrder::Descending => sort_by(array, &|a: &f32, b: &f32| b.total_cmp(a)),
+    }
+}
+
+// NaN以外の値をtotal_cmpの順（降順ならその逆順）でソートし、
+// NaNは符号にかかわらずnansで指定した側に集める
+pub fn sort_f32_total_with_nans(
+    array: &mut [f32],
+    order: &SortOrder,
+    nans: NanPlacement,
+) -> Result<(), SortError> {
+    let is_asc = matches!(*order, SortOrder::Ascending);
+    sort_by(array, &|a: &f32, b: &f32| {
+        compare_with_nans(a.is_nan(), b.is_nan(), nans, || {
+            directed(a.total_cmp(b), is_asc)
+        })
+    })
+}
+
+// sort_f32_totalのf64版
+pub fn sort_f64_total(array: &mut [f64], order: &SortOrder) -> Result<(), SortError> {
+    match *order {
+        SortOrder::Ascending => sort_by(array, &|a: &f64, b: &f64| a.total_cmp(b)),
+        SortOrder::Descending => sort_by(array, &|a: &f64, b: &f64| b.total_cmp(a)),
+    }
+}
+
+// sort_f32_total_with_nansのf64版
+pub fn sort_f64_total_with_nans(
+    array: &mut [f64],
+    order: &SortOrder,
+    nans: NanPlacement,
+) -> Result<(), SortError> {
+    let is_asc = matches!(*order, SortOrder::Ascending);
+    sort_by(array, &|a: &f64, b: &f64| {
+        compare_with_nans(a.is_nan(), b.is_nan(), nans, || {
+            directed(a.total_cmp(b), is_asc)
+        })
+    })
+}
+
+// NaNをnansの側に集めるように比較する。どちらもNaNでないか、どちらもNaNなら
+// compareの結果を使う（NaNどうしの並びも一意に決まる）
+fn compare_with_nans<F>(a_is_nan: bool, b_is_nan: bool, nans: NanPlacement, compare: F) -> Ordering
+where
+    F: FnOnce() -> Ordering,
+{
+    match (a_is_nan, b_is_nan, nans) {
+        (true, false, NanPlacement::First) | (false, true, NanPlacement::Last) => Ordering::Less,
+        (true, false, NanPlacement::Last) | (false, true, NanPlacement::First) => Ordering::Greater,
+        _ => compare(),
+    }
+}
+
+fn directed(ordering: Ordering, is_asc: bool) -> Ordering {
+    if is_asc {
+        ordering
+    } else {
+        ordering.reverse()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        sort_f32_total, sort_f32_total_with_nans, sort_f64_total, sort_f64_total_with_nans,
+        NanPlacement,
+    };
+    use crate::SortOrder::*;
+
+    // NaNを含む配列を==で比較できないので、ビット列で比較する
+    fn bits(x: &[f32]) -> Vec<u32> {
+        x.iter().map(|v| v.to_bits()).collect()
+    }
+
+    fn input() -> Vec<f32> {
+        vec![
+            1.5,
+            f32::NAN,
+            -0.0,
+            f32::INFINITY,
+            0.0,
+            -f32::NAN,
+            f32::NEG_INFINITY,
+            -2.0,
+        ]
+    }
+
+    #[test]
+    fn sort_f32_total_ascending() {
+        let mut x = input();
+        assert_eq!(sort_f32_total(&mut x, &Ascending), Ok(()));
+        let expected = vec![
+            -f32::NAN,
+            f32::NEG_INFINITY,
+            -2.0,
+            -0.0,
+            0.0,
+            1.5,
+            f32::INFINITY,
+            f32::NAN,
+        ];
+        assert_eq!(bits(&x), bits(&expected));
+    }
+
+    #[test]
+    fn sort_f32_total_descending() {
+        let mut x = input();
+        assert_eq!(sort_f32_total(&mut x, &Descending), Ok(()));
+        let expected = vec![
+            f32::NAN,
+            f32::INFINITY,
+            1.5,
+            0.0,
+            -0.0,
+            -2.0,
+            f32::NEG_INFINITY,
+            -f32::NAN,
+        ];
+        assert_eq!(bits(&x), bits(&expected));
+        assert!(sort_f32_total(&mut x[..3], &Descending).is_err());
+    }
+
+    #[test]
+    fn sort_f32_nans_placement() {
+        let mut x = input();
+        assert_eq!(
+            sort_f32_total_with_nans(&mut x, &Ascending, NanPlacement::Last),
+            Ok(())
+        );
+        let expected = vec![
+            f32::NEG_INFINITY,
+            -2.0,
+            -0.0,
+            0.0,
+            1.5,
+            f32::INFINITY,
+            -f32::NAN,
+            f32::NAN,
+        ];
+        assert_eq!(bits(&x), bits(&expected));
+
+        let mut x = input();
+        assert_eq!(
+            sort_f32_total_with_nans(&mut x, &Descending, NanPlacement::First),
+            Ok(())
+        );
+        let expected = vec![
+            f32::NAN,
+            -f32::NAN,
+            f32::INFINITY,
+            1.5,
+            0.0,
+            -0.0,
+            -2.0,
+            f32::NEG_INFINITY,
+        ];
+        assert_eq!(bits(&x), bits(&expected));
+
+        let mut x = input();
+        assert_eq!(
+            sort_f32_total_with_nans(&mut x, &Descending, NanPlacement::Last),
+            Ok(())
+        );
+        assert!(x[..6].iter().all(|v| !v.is_nan()));
+        assert!(x[6..].iter().all(|v| v.is_nan()));
+        assert_eq!(x[0], f32::INFINITY);
+    }
+
+    #[test]
+    fn sort_f64_total_and_nans() {
+        let mut x = vec![f64::NAN, 3.0, -0.0, 0.0];
+        assert_eq!(sort_f64_total(&mut x, &Ascending), Ok(()));
+        assert!(x[3].is_nan());
+        assert_eq!(x[0].to_bits(), (-0.0f64).to_bits());
+
+        let mut x = vec![3.0, f64::NAN, f64::NEG_INFINITY, 1.0];
+        assert_eq!(
+            sort_f64_total_with_nans(&mut x, &Ascending, NanPlacement::First),
+            Ok(())
+        );
+        assert!(x[0].is_nan());
+        assert_eq!(&x[1..], &[f64::NEG_INFINITY, 1.0, 3.0]);
+    }
+}
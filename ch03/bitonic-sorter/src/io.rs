@@ -0,0 +1,190 @@
+use std::fmt;
+use std::io::{self, BufRead, BufReader, BufWriter, Read, Write};
+use std::str::FromStr;
+
+// u32の配列を読み書きするときの形式
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+    Text,     // 1行に1つの10進数
+    BinaryLe, // 4バイトずつのリトルエンディアン
+    BinaryBe, // 4バイトずつのビッグエンディアン
+}
+
+// コマンドライン引数の"text"、"binary-le"、"binary-be"から形式を得る
+impl FromStr for Format {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "text" => Ok(Format::Text),
+            "binary-le" => Ok(Format::BinaryLe),
+            "binary-be" => Ok(Format::BinaryBe),
+            _ => Err(format!(
+                "unknown format: {} (expected text, binary-le or binary-be)",
+                s
+            )),
+        }
+    }
+}
+
+impl fmt::Display for Format {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match *self {
+            Format::Text => "text",
+            Format::BinaryLe => "binary-le",
+            Format::BinaryBe => "binary-be",
+        };
+        f.write_str(name)
+    }
+}
+
+// readerから、指定した形式でu32の配列を読み込む
+pub fn read_u32<R: Read>(reader: R, format: Format) -> io::Result<Vec<u32>> {
+    match format {
+        Format::Text => read_u32_text(reader),
+        Format::BinaryLe => read_u32_le(reader),
+        Format::BinaryBe => read_u32_be(reader),
+    }
+}
+
+// writerへ、指定した形式でu32の配列を書き出す
+pub fn write_u32<W: Write>(writer: W, values: &[u32], format: Format) -> io::Result<()> {
+    match format {
+        Format::Text => write_u32_text(writer, values),
+        Format::BinaryLe => write_u32_le(writer, values),
+        Format::BinaryBe => write_u32_be(writer, values),
+    }
+}
+
+// 4バイトずつのリトルエンディアンの値を、終わりまで読み込む
+// 長さが4の倍数でなければInvalidDataエラーを返す
+pub fn read_u32_le<R: Read>(reader: R) -> io::Result<Vec<u32>> {
+    read_u32_binary(reader, u32::from_le_bytes)
+}
+
+pub fn read_u32_be<R: Read>(reader: R) -> io::Result<Vec<u32>> {
+    read_u32_binary(reader, u32::from_be_bytes)
+}
+
+// 各値を4バイトのリトルエンディアンで書き出す
+pub fn write_u32_le<W: Write>(writer: W, values: &[u32]) -> io::Result<()> {
+    write_u32_binary(writer, values, u32::to_le_bytes)
+}
+
+pub fn write_u32_be<W: Write>(writer: W, values: &[u32]) -> io::Result<()> {
+    write_u32_binary(writer, values, u32::to_be_bytes)
+}
+
+// 1行に1つの10進数を読み込む。空行は読み飛ばし、数として読めない行があればInvalidDataエラーを返す
+pub fn read_u32_text<R: Read>(reader: R) -> io::Result<Vec<u32>> {
+    let mut values = Vec::new();
+    for (i, line) in BufReader::new(reader).lines().enumerate() {
+        let line = line?;
+        let line = line.trim();
+        if line.is_empty() {
+            continue;
+        }
+        let value = line.parse().map_err(|err| {
+            io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("line {}: {:?}: {}", i + 1, line, err),
+            )
+        })?;
+        values.push(value);
+    }
+    Ok(values)
+}
+
+// 1行に1つの10進数を書き出す
+pub fn write_u32_text<W: Write>(writer: W, values: &[u32]) -> io::Result<()> {
+    let mut writer = BufWriter::new(writer);
+    for value in values {
+        writeln!(writer, "{}", value)?;
+    }
+    writer.flush()
+}
+
+fn read_u32_binary<R, D>(mut reader: R, decode: D) -> io::Result<Vec<u32>>
+where
+    R: Read,
+    D: Fn([u8; 4]) -> u32,
+{
+    let mut bytes = Vec::new();
+    reader.read_to_end(&mut bytes)?;
+    if bytes.len() % 4 != 0 {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("the length is not a multiple of 4 bytes: {}", bytes.len()),
+        ));
+    }
+    Ok(bytes
+        .chunks_exact(4)
+        .map(|chunk| decode([chunk[0], chunk[1], chunk[2], chunk[3]]))
+        .collect())
+}
+
+fn write_u32_binary<W, E>(writer: W, values: &[u32], encode: E) -> io::Result<()>
+where
+    W: Write,
+    E: Fn(u32) -> [u8; 4],
+{
+    let mut writer = BufWriter::new(writer);
+    for &value in values {
+        writer.write_all(&encode(value))?;
+    }
+    writer.flush()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        read_u32, read_u32_be, read_u32_le, read_u32_text, write_u32, write_u32_be, write_u32_le,
+        Format,
+    };
+    use crate::parallel::sort;
+    use crate::utils::{is_sorted_ascending, new_u32_vec};
+    use crate::SortOrder::*;
+    use std::io;
+
+    #[test]
+    fn byte_order() {
+        let mut le = Vec::new();
+        write_u32_le(&mut le, &[0x0102_0304, 5]).unwrap();
+        assert_eq!(le, vec![4, 3, 2, 1, 5, 0, 0, 0]);
+        let mut be = Vec::new();
+        write_u32_be(&mut be, &[0x0102_0304, 5]).unwrap();
+        assert_eq!(be, vec![1, 2, 3, 4, 0, 0, 0, 5]);
+        assert_eq!(read_u32_le(&le[..]).unwrap(), vec![0x0102_0304, 5]);
+        assert_eq!(read_u32_be(&be[..]).unwrap(), vec![0x0102_0304, 5]);
+    }
+
+    #[test]
+    fn round_trip_with_sort() {
+        let input = new_u32_vec(1024);
+        for &format in &[Format::BinaryLe, Format::BinaryBe, Format::Text] {
+            let mut bytes = Vec::new();
+            write_u32(&mut bytes, &input, format).unwrap();
+            let mut x = read_u32(&bytes[..], format).unwrap();
+            assert_eq!(x, input, "format: {}", format);
+
+            assert_eq!(sort(&mut x, &Ascending), Ok(()));
+            let mut sorted = Vec::new();
+            write_u32(&mut sorted, &x, format).unwrap();
+            let y = read_u32(&sorted[..], format).unwrap();
+            assert!(is_sorted_ascending(&y));
+            assert_eq!(y, x);
+        }
+    }
+
+    #[test]
+    fn invalid_input() {
+        let err = read_u32_le(&[1u8, 2, 3, 4, 5][..]).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+        let err = read_u32_text(&b"1\nabc\n"[..]).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+        assert_eq!(read_u32_text(&b"3\n\n1\n"[..]).unwrap(), vec![3, 1]);
+
+        assert_eq!("binary-be".parse(), Ok(Format::BinaryBe));
+        assert!("binary".parse::<Format>().is_err());
+    }
+}
@@ -1,3 +1,6 @@
+use std::fmt;
+use std::ops::Add;
+
 pub mod utils;
 
 // 第1段階：初歩的な実装。u32型の値のソートのみに対応
@@ -14,7 +17,202 @@ pub mod fourth;
 
 pub mod parallel;
 
+// 順次処理のソート。parallelモジュールと同じエラー型と、比較・交換の回数の計測に対応
+pub mod seq;
+
+// Batcherの奇偶マージソート。バイトニックソートと比べるための、別のソーティングネットワーク
+pub mod oddeven;
+
+// rayonを使わずに、決まった数のstd::threadのスレッドでソートする
+pub mod threads;
+
+// 行優先（row-major）で格納された2次元配列の行ごと・列ごとのソート
+pub mod matrix;
+
+// 値を追加していき、最後にソート済みのベクタとして取り出すバッファと、
+// 常に昇順に並んだベクタ
+pub mod buffer;
+
+// 同じ入力を何度もソートするときに、前回の結果を使い回すソーター
+pub mod cache;
+
+// u32の配列をテキストやバイナリ（リトルエンディアン、ビッグエンディアン）で読み書きする
+pub mod io;
+
+// CSVファイルの行を、指定した列の値でソートする
+#[cfg(feature = "csv")]
+pub mod csv;
+
+// 整数の基数ソート（バイトニックソートの代わりに使える、整数専用の高速なソート）
+pub mod radix;
+
+// SIMD命令を使う、u32のキーによる構造体の高速なソート
+#[cfg(feature = "simd")]
+pub mod simd;
+
+// NaNを含む浮動小数点数のソート
+pub mod float;
+
+// ソーティングネットワークの性質を調べるための関数
+pub mod analysis;
+
+// ICUの照合規則による、ロケールを考慮した文字列のソート
+#[cfg(feature = "icu")]
+pub mod collation;
+
+// Unicodeの正規化（NFC）をしてから比較する、文字列のソート
+#[cfg(feature = "unicode-norm")]
+pub mod normalization;
+
 pub enum SortOrder {
     Ascending,   // 昇順
     Descending,  // 降順
 }
+
+// ソート中に行った比較と交換の回数、および並列に実行したタスク（rayon::join）の数
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct SortMetrics {
+    pub comparisons: u64,
+    pub swaps: u64,
+    pub parallel_tasks: u64,
+}
+
+impl SortMetrics {
+    fn with_parallel_task(self) -> Self {
+        Self {
+            parallel_tasks: self.parallel_tasks + 1,
+            ..self
+        }
+    }
+}
+
+impl Add for SortMetrics {
+    type Output = Self;
+
+    fn add(self, other: Self) -> Self {
+        Self {
+            comparisons: self.comparisons + other.comparisons,
+            swaps: self.swaps + other.swaps,
+            parallel_tasks: self.parallel_tasks + other.parallel_tasks,
+        }
+    }
+}
+
+// ソートに失敗したときのエラー
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SortError {
+    // 要素数が2のべき乗になっていない
+    // suggestedはlen以上で最小の2のべき乗。この長さまでパディングすればソートできる
+    NotPowerOfTwo { len: usize, suggested: usize },
+    // 要素数が行の幅（width）の倍数になっていない
+    NotMultipleOfWidth { len: usize, width: usize },
+    // 一緒に並べ替える2つの配列の要素数が異なる
+    LengthMismatch { keys: usize, values: usize },
+    // SortControlでソートが取り消された
+    Cancelled,
+    // SortControlで指定した期限までにソートが終わらなかった
+    TimedOut,
+    // ロケール識別子が不正、または対応する照合規則がない
+    #[cfg(feature = "icu")]
+    InvalidLocale { locale: String },
+}
+
+impl fmt::Display for SortError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            SortError::NotPowerOfTwo { len, suggested } => write!(
+                f,
+                "The length of x is not a power of two. (x.len(): {}) \
+                 Pad it to {} elements to sort it.",
+                len, suggested
+            ),
+            SortError::NotMultipleOfWidth { len, width } => write!(
+                f,
+                "The length of data is not a multiple of the width. (data.len(): {}, width: {})",
+                len, width
+            ),
+            SortError::LengthMismatch { keys, values } => write!(
+                f,
+                "The lengths of keys and values differ. (keys.len(): {}, values.len(): {})",
+                keys, values
+            ),
+            SortError::Cancelled => write!(f, "The sort was cancelled."),
+            SortError::TimedOut => write!(f, "The sort did not finish before the deadline."),
+            #[cfg(feature = "icu")]
+            SortError::InvalidLocale { ref locale } => write!(f, "Unsupported locale: {}", locale),
+        }
+    }
+}
+
+impl SortError {
+    // 要素数lenに対するNotPowerOfTwoエラーを、パディング後の長さの提案付きで作る
+    // 2のべき乗に切り上げるとusizeに収まらないときは、提案できないのでsuggestedを0にする
+    pub fn not_power_of_two(len: usize) -> Self {
+        SortError::NotPowerOfTwo {
+            len,
+            suggested: len.checked_next_power_of_two().unwrap_or(0),
+        }
+    }
+}
+
+impl std::error::Error for SortError {}
+
+// ファイル処理などのコードで`?`演算子を使えるようにio::Errorへ変換する
+impl From<SortError> for std::io::Error {
+    fn from(err: SortError) -> Self {
+        match err {
+            SortError::NotPowerOfTwo { .. }
+            | SortError::NotMultipleOfWidth { .. }
+            | SortError::LengthMismatch { .. } => {
+                std::io::Error::new(std::io::ErrorKind::InvalidInput, err)
+            }
+            SortError::Cancelled => std::io::Error::new(std::io::ErrorKind::Interrupted, err),
+            SortError::TimedOut => std::io::Error::new(std::io::ErrorKind::TimedOut, err),
+            #[cfg(feature = "icu")]
+            SortError::InvalidLocale { .. } => {
+                std::io::Error::new(std::io::ErrorKind::InvalidInput, err)
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::SortError;
+    use std::io;
+
+    #[test]
+    fn sort_error_into_io_error() {
+        let err: io::Error = SortError::not_power_of_two(3).into();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+        assert_eq!(
+            err.to_string(),
+            "The length of x is not a power of two. (x.len(): 3) Pad it to 4 elements to sort it."
+        );
+    }
+
+    #[test]
+    fn not_power_of_two_suggests_padded_length() {
+        assert_eq!(
+            SortError::not_power_of_two(11),
+            SortError::NotPowerOfTwo {
+                len: 11,
+                suggested: 16
+            }
+        );
+        assert_eq!(
+            SortError::not_power_of_two(0),
+            SortError::NotPowerOfTwo {
+                len: 0,
+                suggested: 1
+            }
+        );
+        assert_eq!(
+            SortError::not_power_of_two(usize::MAX),
+            SortError::NotPowerOfTwo {
+                len: usize::MAX,
+                suggested: 0
+            }
+        );
+    }
+}
@@ -0,0 +1,167 @@
+use super::parallel::sort;
+use super::{SortError, SortOrder};
+use rayon::prelude::*;
+
+// 幅width、高さdata.len() / widthの行優先の2次元配列について、各行を独立にソートする
+// 行は互いに重ならないので、行ごとに並列にソートする
+// widthは2のべき乗、data.len()はwidthの倍数でなければならない
+pub fn sort_rows<T: Ord + Send>(
+    data: &mut [T],
+    width: usize,
+    order: &SortOrder,
+) -> Result<(), SortError> {
+    check_shape(data.len(), width)?;
+    data.par_chunks_mut(width)
+        .try_for_each(|row| sort(row, order))
+}
+
+// 幅widthの行優先の2次元配列について、各列を独立にソートする
+// 列の要素は飛び飛び（strided）に並んでいるので、列ごとに作業用のバッファへ集めて
+// ソートしてから書き戻す。列ごとの処理は並列に行う
+// 列の長さ（行数）は2のべき乗、data.len()はwidthの倍数でなければならない
+// widthは2のべき乗でなくてもよい
+pub fn sort_columns<T: Ord + Send + Sync + Copy>(
+    data: &mut [T],
+    width: usize,
+    order: &SortOrder,
+) -> Result<(), SortError> {
+    if width == 0 || !data.len().is_multiple_of(width) {
+        return Err(SortError::NotMultipleOfWidth {
+            len: data.len(),
+            width,
+        });
+    }
+    let height = data.len() / width;
+    if !height.is_power_of_two() {
+        return Err(SortError::not_power_of_two(height));
+    }
+
+    let source = &*data;
+    let columns = (0..width)
+        .into_par_iter()
+        .map(|column| {
+            let mut buffer: Vec<T> = source.iter().skip(column).step_by(width).copied().collect();
+            sort(&mut buffer, order).map(|_| buffer)
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+
+    data.par_chunks_mut(width)
+        .enumerate()
+        .for_each(|(row_index, row)| {
+            for (value, column) in row.iter_mut().zip(&columns) {
+                *value = column[row_index];
+            }
+        });
+    Ok(())
+}
+
+fn check_shape(len: usize, width: usize) -> Result<(), SortError> {
+    if !width.is_power_of_two() {
+        Err(SortError::not_power_of_two(width))
+    } else if !len.is_multiple_of(width) {
+        Err(SortError::NotMultipleOfWidth { len, width })
+    } else {
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{sort_columns, sort_rows};
+    use crate::SortError;
+    use crate::SortOrder::*;
+
+    #[test]
+    fn sort_each_row() {
+        // 幅4、高さ3の行列
+        let mut data = vec![
+            4, 3, 2, 1, //
+            9, 12, 10, 11, //
+            0, 8, 7, 5,
+        ];
+        assert_eq!(sort_rows(&mut data, 4, &Ascending), Ok(()));
+        // 行をまたいで値が移動していない
+        assert_eq!(
+            data,
+            vec![
+                1, 2, 3, 4, //
+                9, 10, 11, 12, //
+                0, 5, 7, 8,
+            ]
+        );
+
+        assert_eq!(sort_rows(&mut data, 4, &Descending), Ok(()));
+        assert_eq!(
+            data,
+            vec![
+                4, 3, 2, 1, //
+                12, 11, 10, 9, //
+                8, 7, 5, 0,
+            ]
+        );
+    }
+
+    #[test]
+    fn sort_rows_invalid_shape() {
+        let mut data = vec![1, 2, 3, 4, 5, 6];
+        assert_eq!(
+            sort_rows(&mut data, 3, &Ascending),
+            Err(SortError::not_power_of_two(3))
+        );
+        assert_eq!(
+            sort_rows(&mut data, 4, &Ascending),
+            Err(SortError::NotMultipleOfWidth { len: 6, width: 4 })
+        );
+    }
+
+    #[test]
+    fn sort_each_column() {
+        // 幅3、高さ4の行列。幅は2のべき乗でなくてもよい
+        let mut data = vec![
+            4, 30, 200, //
+            1, 10, 300, //
+            3, 40, 100, //
+            2, 20, 400,
+        ];
+        assert_eq!(sort_columns(&mut data, 3, &Ascending), Ok(()));
+        // 列をまたいで値が移動していない
+        assert_eq!(
+            data,
+            vec![
+                1, 10, 100, //
+                2, 20, 200, //
+                3, 30, 300, //
+                4, 40, 400,
+            ]
+        );
+
+        assert_eq!(sort_columns(&mut data, 3, &Descending), Ok(()));
+        assert_eq!(
+            data,
+            vec![
+                4, 40, 400, //
+                3, 30, 300, //
+                2, 20, 200, //
+                1, 10, 100,
+            ]
+        );
+    }
+
+    #[test]
+    fn sort_columns_invalid_shape() {
+        let mut data = vec![1, 2, 3, 4, 5, 6];
+        // 高さ3の列はソートできない
+        assert_eq!(
+            sort_columns(&mut data, 2, &Ascending),
+            Err(SortError::not_power_of_two(3))
+        );
+        assert_eq!(
+            sort_columns(&mut data, 4, &Ascending),
+            Err(SortError::NotMultipleOfWidth { len: 6, width: 4 })
+        );
+        assert_eq!(
+            sort_columns(&mut data, 0, &Ascending),
+            Err(SortError::NotMultipleOfWidth { len: 6, width: 0 })
+        );
+    }
+}
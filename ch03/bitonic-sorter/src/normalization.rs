@@ -0,0 +1,96 @@
+use super::parallel::{sort, sort_by_external_keys};
+use super::{SortError, SortOrder};
+use unicode_normalization::UnicodeNormalization;
+
+// 文字列をUnicodeの正規化形式NFCにそろえた形で比較してソートする
+// "é"には1文字（U+00E9）で表す合成済みの形と、"e"と結合文字（U+0301）で表す
+// 分解された形があり、str::cmpではこれらが別の文字列として比較されてしまう
+// 正規化した文字列は最初に要素ごとに一度だけ作り、arrayの文字列そのものは変更しない
+// 正規化すると等しくなる文字列どうしは元の順序が保たれる（安定ソート）
+pub fn sort_normalized(array: &mut [String], order: &SortOrder) -> Result<(), SortError> {
+    let keys: Vec<String> = array.iter().map(|s| s.nfc().collect()).collect();
+    sort_by_external_keys(array, &keys, order)
+}
+
+// sort_normalizedと同じ順にソートするが、arrayの文字列をNFCに書き換えてから並べる
+// 正規化した文字列を別に持たなくて済むが、元の表記（分解された形など）は失われる
+pub fn normalize_and_sort(array: &mut [String], order: &SortOrder) -> Result<(), SortError> {
+    for s in array.iter_mut() {
+        *s = s.nfc().collect();
+    }
+    sort(array, order)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{normalize_and_sort, sort_normalized};
+    use crate::SortError;
+    use crate::SortOrder::*;
+
+    // 合成済みの"é"（U+00E9）と、"e"に結合アクセント（U+0301）をつけた分解された"é"
+    const COMPOSED: &str = "caf\u{e9}";
+    const DECOMPOSED: &str = "cafe\u{301}";
+
+    fn words() -> Vec<String> {
+        vec![
+            DECOMPOSED.to_string(),
+            "caff".to_string(),
+            COMPOSED.to_string(),
+            "cafe".to_string(),
+        ]
+    }
+
+    #[test]
+    fn composed_and_decomposed_are_equal() {
+        // str::cmpでは"cafe\u{301}"が"caff"より前に来るが、
+        // NFCでは"caf\u{e9}"と等しくなり、"caff"より後ろに並ぶ
+        let mut x = words();
+        assert_eq!(sort_normalized(&mut x, &Ascending), Ok(()));
+        // 等しいとみなされた2つは元の順序（分解された形が先）のまま並ぶ
+        assert_eq!(x, vec!["cafe", "caff", DECOMPOSED, COMPOSED]);
+
+        let mut x = vec![
+            COMPOSED.to_string(),
+            "caff".to_string(),
+            DECOMPOSED.to_string(),
+            "cafe".to_string(),
+        ];
+        assert_eq!(sort_normalized(&mut x, &Ascending), Ok(()));
+        assert_eq!(x, vec!["cafe", "caff", COMPOSED, DECOMPOSED]);
+    }
+
+    #[test]
+    fn differs_from_str_cmp() {
+        // str::cmpでは2つの"é"の間に"caff"が入ってしまう
+        let mut x = words();
+        x.sort();
+        assert_eq!(x, vec!["cafe", DECOMPOSED, "caff", COMPOSED]);
+    }
+
+    #[test]
+    fn sort_normalized_descending() {
+        let mut x = words();
+        assert_eq!(sort_normalized(&mut x, &Descending), Ok(()));
+        assert_eq!(x, vec![DECOMPOSED, COMPOSED, "caff", "cafe"]);
+    }
+
+    #[test]
+    fn normalize_and_sort_rewrites_strings() {
+        let mut x = words();
+        assert_eq!(normalize_and_sort(&mut x, &Ascending), Ok(()));
+        assert_eq!(x, vec!["cafe", "caff", COMPOSED, COMPOSED]);
+    }
+
+    #[test]
+    fn sort_normalized_fails_on_non_power_of_two() {
+        let mut x = words();
+        x.pop();
+        assert_eq!(
+            sort_normalized(&mut x, &Ascending),
+            Err(SortError::NotPowerOfTwo {
+                len: 3,
+                suggested: 4
+            })
+        );
+    }
+}
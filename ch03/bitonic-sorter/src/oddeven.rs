@@ -0,0 +1,188 @@
+use super::utils::require_power_of_two;
+use super::{SortError, SortMetrics, SortOrder};
+use std::cmp::Ordering;
+
+// Batcherの奇偶マージソート（odd-even mergesort）
+// バイトニックソートと同じく比較の順序が入力によらないソーティングネットワークだが、
+// 比較は常に「小さい方を前の位置に置く」向きで行い、比較の回数はバイトニックより少ない
+// （n = 8ならバイトニックの24回に対して19回）
+// 教材としてネットワークの構造を比べられるように、seqモジュールと同じく順次処理で実装する
+
+pub fn sort<T: Ord>(array: &mut [T], order: &SortOrder) -> Result<(), SortError> {
+    match *order {
+        SortOrder::Ascending => sort_by(array, &|a, b| a.cmp(b)),
+        SortOrder::Descending => sort_by(array, &|a, b| b.cmp(a)),
+    }
+}
+
+pub fn sort_by<T, F>(array: &mut [T], comparator: &F) -> Result<(), SortError>
+where
+    F: Fn(&T, &T) -> Ordering,
+{
+    sort_by_observed(array, comparator, &|_, _| ()).map(|_| ())
+}
+
+// sort_byと同じようにソートし、比較と交換の回数を返す
+pub fn sort_by_counting<T, F>(array: &mut [T], comparator: &F) -> Result<SortMetrics, SortError>
+where
+    F: Fn(&T, &T) -> Ordering,
+{
+    sort_by_observed(array, comparator, &|_, _| ())
+}
+
+// parallel::sort_by_observedと同じく、ネットワークが比較を行うたびに、
+// 比較した2要素の位置(i, j)（i < j）をobserverに通知する
+// 順次処理なので、通知の順序は常に一定になる
+pub fn sort_by_observed<T, F, O>(
+    array: &mut [T],
+    comparator: &F,
+    observer: &O,
+) -> Result<SortMetrics, SortError>
+where
+    F: Fn(&T, &T) -> Ordering,
+    O: Fn(usize, usize),
+{
+    require_power_of_two(array.len())?;
+    let n = array.len();
+    let mut metrics = SortMetrics::default();
+
+    // 長さpのソート済みの列を2つずつマージして、長さ2pのソート済みの列にしていく
+    let mut p = 1;
+    while p < n {
+        // 距離kだけ離れた要素どうしを比較する。ただし比較するのは、
+        // 同じ長さ2pのブロックに属する2要素だけ
+        let mut k = p;
+        while k >= 1 {
+            let mut j = k % p;
+            while j + k < n {
+                for i in 0..k.min(n - j - k) {
+                    let (a, b) = (i + j, i + j + k);
+                    if a / (2 * p) == b / (2 * p) {
+                        observer(a, b);
+                        compare_and_swap(array, a, b, comparator, &mut metrics);
+                    }
+                }
+                j += 2 * k;
+            }
+            k /= 2;
+        }
+        p *= 2;
+    }
+    Ok(metrics)
+}
+
+fn compare_and_swap<T, F>(
+    array: &mut [T],
+    i: usize,
+    j: usize,
+    comparator: &F,
+    metrics: &mut SortMetrics,
+) where
+    F: Fn(&T, &T) -> Ordering,
+{
+    metrics.comparisons += 1;
+    if comparator(&array[i], &array[j]) == Ordering::Greater {
+        array.swap(i, j);
+        metrics.swaps += 1;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{sort, sort_by_counting, sort_by_observed};
+    use crate::analysis::is_sorting_network;
+    use crate::parallel;
+    use crate::utils::{is_sorted_ascending, is_sorted_descending, new_u32_vec};
+    use crate::SortError;
+    use crate::SortOrder::*;
+    use std::cell::RefCell;
+
+    #[test]
+    fn sort_u32() {
+        let mut x = new_u32_vec(1024);
+        assert_eq!(sort(&mut x, &Ascending), Ok(()));
+        assert!(is_sorted_ascending(&x));
+        assert_eq!(sort(&mut x, &Descending), Ok(()));
+        assert!(is_sorted_descending(&x));
+        assert_eq!(
+            sort(&mut x[..3], &Ascending),
+            Err(SortError::not_power_of_two(3))
+        );
+    }
+
+    #[test]
+    fn agrees_with_parallel() {
+        for &bits in &[0, 1, 3, 10, 14] {
+            let input = new_u32_vec(1 << bits);
+            let mut x = input.clone();
+            sort(&mut x, &Ascending).unwrap();
+            let mut y = input;
+            parallel::sort(&mut y, &Ascending).unwrap();
+            assert_eq!(x, y);
+        }
+    }
+
+    #[test]
+    fn observed_sequence_differs_from_bitonic() {
+        let input = vec![10, 30, 11, 20, 4, 330, 21, 110];
+
+        let odd_even = RefCell::new(Vec::new());
+        let mut x = input.clone();
+        let metrics = sort_by_observed(&mut x, &|a, b| a.cmp(b), &|i, j| {
+            odd_even.borrow_mut().push((i, j))
+        })
+        .unwrap();
+        let odd_even = odd_even.into_inner();
+        assert_eq!(x, vec![4, 10, 11, 20, 21, 30, 110, 330]);
+        assert_eq!(
+            odd_even,
+            vec![
+                (0, 1),
+                (2, 3),
+                (4, 5),
+                (6, 7),
+                (0, 2),
+                (1, 3),
+                (4, 6),
+                (5, 7),
+                (1, 2),
+                (5, 6),
+                (0, 4),
+                (1, 5),
+                (2, 6),
+                (3, 7),
+                (2, 4),
+                (3, 5),
+                (1, 2),
+                (3, 4),
+                (5, 6),
+            ]
+        );
+        assert_eq!(metrics.comparisons, 19);
+        assert!(is_sorting_network(&odd_even, 8));
+
+        let bitonic = std::sync::Mutex::new(Vec::new());
+        let mut y = input;
+        parallel::sort_by_observed(&mut y, &|a, b| a.cmp(b), &|i, j| {
+            bitonic.lock().unwrap().push((i, j))
+        })
+        .unwrap();
+        let bitonic = bitonic.into_inner().unwrap();
+        assert_eq!(y, x);
+        assert_eq!(bitonic.len(), 24);
+        assert_ne!(odd_even, bitonic);
+    }
+
+    #[test]
+    fn fewer_comparisons_than_bitonic() {
+        for &bits in &[2, 4, 8, 12] {
+            let mut x = new_u32_vec(1 << bits);
+            let mut y = x.clone();
+            let odd_even = sort_by_counting(&mut x, &|a, b| a.cmp(b)).unwrap();
+            let bitonic = parallel::sort_by_counting(&mut y, &|a, b| a.cmp(b)).unwrap();
+            assert_eq!(x, y);
+            assert!(odd_even.comparisons < bitonic.comparisons);
+            assert_eq!(odd_even.parallel_tasks, 0);
+        }
+    }
+}
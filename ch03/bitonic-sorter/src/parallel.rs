@@ -1,10 +1,76 @@
-use super::SortOrder;
+use super::utils::{is_sorted_ascending, is_sorted_descending, log2_exact, require_power_of_two};
+use super::{seq, SortError, SortMetrics, SortOrder};
 use rayon;
+use rayon::prelude::*;
+use std::borrow::Cow;
 use std::cmp::Ordering;
+use std::collections::{BinaryHeap, HashMap, VecDeque};
+use std::mem::MaybeUninit;
+use std::ops::{IndexMut, Range};
+use std::sync::atomic::{AtomicBool, Ordering as AtomicOrdering};
+use std::sync::mpsc::{self, Receiver};
+use std::sync::{Arc, OnceLock};
+use std::thread;
+use std::time::{Duration, Instant};
 
+// 並列に処理するかを決める、しきい値（要素のサイズが8バイト以下のとき）
+// 環境変数BITONIC_THRESHOLDで、再コンパイルせずに変更できる（base_thresholdを参照）
+// 元の実装と同じく、do_sortでは2つに分けた長さがしきい値を超えるとき、
+// sub_sortではしきい値以上のときに並列に処理する
 const PARALLEL_THRESHOLD: usize = 4096;
+// しきい値を指定する環境変数の名前
+const THRESHOLD_ENV_VAR: &str = "BITONIC_THRESHOLD";
+// しきい値の下限。これより小さな部分配列ではタスクの生成コストが上回る
+const MIN_PARALLEL_THRESHOLD: usize = 256;
 
-pub fn sort<T: Ord + Send>(array: &mut [T], order: &SortOrder) -> Result<(), String> {
+// 要素型Tのサイズに応じたしきい値を返す
+// 要素が大きいほど1回の交換（swap）にかかる時間が長くなり、タスクを生成する
+// オーバーヘッドが相対的に小さくなるので、しきい値を要素のサイズに反比例して下げる
+// 例：8バイト以下 → 4096、64バイト → 512、256バイト以上 → 256
+// （examples/payload_benchmark.rsで要素のサイズごとの速度を計測できる）
+pub fn parallel_threshold<T>() -> usize {
+    let size = std::mem::size_of::<T>();
+    let base = base_threshold();
+    if size <= 8 {
+        base
+    } else {
+        (base * 8 / size).max(MIN_PARALLEL_THRESHOLD)
+    }
+}
+
+// 要素のサイズが8バイト以下のときのしきい値を返す
+// 最初に呼ばれたときに一度だけ環境変数BITONIC_THRESHOLDを読み、その値を使い続ける
+// 環境変数が設定されていなければPARALLEL_THRESHOLDを使う。1以上の整数でない値が
+// 設定されていたら、標準エラー出力に警告を出してPARALLEL_THRESHOLDを使う
+// （最初の呼び出しでは環境変数の読み取りのためにメモリを確保する）
+fn base_threshold() -> usize {
+    static THRESHOLD: OnceLock<usize> = OnceLock::new();
+    *THRESHOLD.get_or_init(|| {
+        let value = std::env::var(THRESHOLD_ENV_VAR).ok();
+        parse_threshold(value.as_deref()).unwrap_or_else(|invalid| {
+            eprintln!(
+                "warning: ignoring invalid {}={:?}, using {}",
+                THRESHOLD_ENV_VAR, invalid, PARALLEL_THRESHOLD
+            );
+            PARALLEL_THRESHOLD
+        })
+    })
+}
+
+// 環境変数の値からしきい値を求める。値が不正ならその値をエラーとして返す
+fn parse_threshold(value: Option<&str>) -> Result<usize, &str> {
+    match value {
+        None => Ok(PARALLEL_THRESHOLD),
+        Some(value) => match value.trim().parse() {
+            Ok(threshold) if threshold > 0 => Ok(threshold),
+            _ => Err(value),
+        },
+    }
+}
+
+// 戻り値のResultは#[must_use]なので、sort(&mut x, &order);のように結果を捨てると
+// コンパイラが警告する（要素数が2のべき乗でなかったことに気づかずに進むのを防ぐ）
+pub fn sort<T: Ord + Send>(array: &mut [T], order: &SortOrder) -> Result<(), SortError> {
     // do_sortを呼ぶ代わりに、sort_by を呼ぶようにする
     // is_power_of_twoはsort_byが呼ぶので、ここからは削除した
     match *order {
@@ -14,23 +80,411 @@ pub fn sort<T: Ord + Send>(array: &mut [T], order: &SortOrder) -> Result<(), Str
     }
 }
 
-pub fn sort_by<T, F>(array: &mut [T], comparator: &F) -> Result<(), String>
+// sortと同じだが、ソートできなければ（要素数が2のべき乗でなければ）パニックする
+// 呼び出し側で要素数が2のべき乗であることが分かっていて、エラーを処理したくないときに使う
+// パニックのメッセージはSortErrorの表示と同じで、呼び出した位置が報告される
+#[track_caller]
+pub fn sort_or_panic<T: Ord + Send>(array: &mut [T], order: &SortOrder) {
+    if let Err(err) = sort(array, order) {
+        panic!("{}", err);
+    }
+}
+
+// sort、sort_byとその内部のdo_sort、sub_sort、compare_and_swapは配列をその場で
+// 並べ替えるだけで、ヒープ領域を一切確保しない（tests/no_alloc.rsで検証している）
+// ただし、しきい値以上の配列を並列に処理するときは、rayonのスレッドプールが
+// 内部でメモリを確保することがある
+//
+// comparatorはSyncであればよいので、照合表のような外部のデータ（コンテキスト）を
+// 参照で捕捉したクロージャも渡せる。例：`sort_by(&mut x, &|a, b| table.compare(a, b))`
+// このときコンテキストの型もSyncである必要がある（RefCellなどを含む型は渡せない）
+pub fn sort_by<T, F>(array: &mut [T], comparator: &F) -> Result<(), SortError>
+where
+    T: Send,
+    F: Sync + Fn(&T, &T) -> Ordering,
+{
+    sort_by_counting(array, comparator).map(|_| ())
+}
+
+// sort_byと同じ順序にソートするが、1つのスレッドだけで処理する（seq::sort_byを使う）
+// sort_byはcomparatorを複数のスレッドから呼ぶので、comparatorにSyncを求める
+// Rc<Cell<_>>（呼び出し回数を数えるなど）やRefCellを捕捉したクロージャはSyncでないので
+// sort_byには渡せないが、この関数になら渡せる。要素の型TにもSendは要らない
+// 並列には処理しないので、大きな配列ではsort_byより遅い
+pub fn sort_by_local<T, F>(array: &mut [T], comparator: &F) -> Result<(), SortError>
+where
+    F: Fn(&T, &T) -> Ordering,
+{
+    seq::sort_by(array, comparator)
+}
+
+// 非同期のコードから呼ぶための、sort_byのラッパー
+// CPUを使い続けるソートをtokioのブロッキング用のスレッド（spawn_blocking）で行い、
+// 非同期のタスクを処理するスレッドを止めないようにする。ソートしたベクタを返す
+// 返すFutureは最初にpollされたときにソートを始めるので、tokioのランタイムの中でawaitすること
+// ランタイムが終了してソートが取り消されたときはSortError::Cancelledを返し、
+// ソート中にcomparatorがパニックしたときは、awaitした側で同じパニックを起こす
+#[cfg(feature = "tokio")]
+pub async fn sort_by_async<T, F>(mut array: Vec<T>, comparator: F) -> Result<Vec<T>, SortError>
+where
+    T: Send + 'static,
+    F: Sync + Send + 'static + Fn(&T, &T) -> Ordering,
+{
+    let task =
+        tokio::task::spawn_blocking(move || sort_by(&mut array, &comparator).map(|()| array));
+    match task.await {
+        Ok(result) => result,
+        Err(err) if err.is_panic() => std::panic::resume_unwind(err.into_panic()),
+        Err(_) => Err(SortError::Cancelled),
+    }
+}
+
+// sort_byと同じようにソートし、比較と交換の回数などを返す
+pub fn sort_by_counting<T, F>(array: &mut [T], comparator: &F) -> Result<SortMetrics, SortError>
+where
+    T: Send,
+    F: Sync + Fn(&T, &T) -> Ordering,
+{
+    sort_by_threshold_counting(array, comparator, parallel_threshold::<T>())
+}
+
+// 並列に処理するかを決めるしきい値（部分配列の要素数）を指定してソートする
+// しきい値を変えながら速度を測れば（examples/threshold_benchmark.rs）、
+// PARALLEL_THRESHOLDを書き換えずに実行環境に合ったしきい値を探せる
+pub fn sort_by_with_threshold<T, F>(
+    array: &mut [T],
+    comparator: &F,
+    threshold: usize,
+) -> Result<(), SortError>
+where
+    T: Send,
+    F: Sync + Fn(&T, &T) -> Ordering,
+{
+    sort_by_threshold_counting(array, comparator, threshold).map(|_| ())
+}
+
+// タスクを1つ生成して受け渡すのにかかるおおよその時間（ナノ秒）
+// 1要素あたり1ナノ秒で処理できる型では、しきい値がPARALLEL_THRESHOLDと同じになる
+const SPAWN_COST_NS: u64 = 4096;
+
+// 1要素あたりの処理にかかるおおよその時間（ナノ秒）を指定してソートする
+// 部分配列の分割後の要素数とper_element_nsの積がSPAWN_COST_NSを超えるときだけ並列に処理する
+// 比較に時間がかかる場合は大きな値を渡すと、要素数の少ない配列でも並列に処理できる
+// per_element_nsが0なら、並列に処理しない
+pub fn sort_by_with_cost_hint<T, F>(
+    array: &mut [T],
+    comparator: &F,
+    per_element_ns: u32,
+) -> Result<SortMetrics, SortError>
+where
+    T: Send,
+    F: Sync + Fn(&T, &T) -> Ordering,
+{
+    let threshold = match per_element_ns {
+        0 => usize::MAX,
+        ns => SPAWN_COST_NS.div_ceil(ns as u64) as usize,
+    };
+    sort_by_threshold_counting(array, comparator, threshold)
+}
+
+fn sort_by_threshold_counting<T, F>(
+    array: &mut [T],
+    comparator: &F,
+    threshold: usize,
+) -> Result<SortMetrics, SortError>
+where
+    T: Send,
+    F: Sync + Fn(&T, &T) -> Ordering,
+{
+    require_power_of_two(array.len())?;
+    #[cfg(all(feature = "debug-validate", debug_assertions))]
+    validate_comparator(array, comparator);
+    #[cfg(feature = "metrics")]
+    let start = Instant::now();
+    let metrics = match sort_presorted(array, comparator) {
+        Some(metrics) => metrics,
+        None => do_sort(array, true, comparator, Tuning::with_threshold(threshold)),
+    };
+    #[cfg(feature = "metrics")]
+    record_metrics(array.len(), &metrics, start.elapsed());
+    Ok(metrics)
+}
+
+// 比較関数の検査で調べる、要素の3つ組の数
+#[cfg(all(feature = "debug-validate", debug_assertions))]
+const VALIDATE_SAMPLES: usize = 64;
+
+// debug-validateフィーチャーが有効なデバッグビルドで、ソートの前に比較関数を検査する
+// arrayから選んだ要素の3つ組(a, b, c)について、次の性質を確かめ、成り立たなければパニックする
+// - 反射律：compare(a, a)がEqual
+// - 反対称性：compare(a, b)とcompare(b, a)が逆の結果
+// - 推移律：a <= bかつb <= cならa <= c（等しいときも含む）
+// 要素は毎回同じシード値の乱数で選ぶので、同じ入力なら同じ3つ組を調べる
+// すべての組を調べるわけではないので、違反を見逃すことはある
+#[cfg(all(feature = "debug-validate", debug_assertions))]
+fn validate_comparator<T, F>(array: &[T], comparator: &F)
+where
+    F: Fn(&T, &T) -> Ordering,
+{
+    use rand::{Rng, SeedableRng};
+
+    if array.is_empty() {
+        return;
+    }
+    let mut rng = rand_pcg::Pcg64Mcg::from_seed([0; 16]);
+    for _ in 0..VALIDATE_SAMPLES {
+        let (i, j, k) = (
+            rng.gen_range(0, array.len()),
+            rng.gen_range(0, array.len()),
+            rng.gen_range(0, array.len()),
+        );
+        let (a, b, c) = (&array[i], &array[j], &array[k]);
+        assert!(
+            comparator(a, a) == Ordering::Equal,
+            "comparator is not a strict weak ordering: compare(x[{}], x[{}]) is not Equal",
+            i,
+            i
+        );
+        let ab = comparator(a, b);
+        assert!(
+            comparator(b, a) == ab.reverse(),
+            "comparator is not a strict weak ordering: compare(x[{}], x[{}]) is {:?} \
+             but compare(x[{}], x[{}]) is not {:?}",
+            i,
+            j,
+            ab,
+            j,
+            i,
+            ab.reverse()
+        );
+        let bc = comparator(b, c);
+        if ab != Ordering::Greater && bc != Ordering::Greater {
+            // a <= b <= c なら a <= c で、両方がEqualのときだけa == c
+            let expected = if ab == Ordering::Equal && bc == Ordering::Equal {
+                Ordering::Equal
+            } else {
+                Ordering::Less
+            };
+            let ac = comparator(a, c);
+            assert!(
+                ac == expected,
+                "comparator is not a strict weak ordering: x[{}] {:?} x[{}] {:?} x[{}] \
+                 but compare(x[{}], x[{}]) is {:?}",
+                i,
+                ab,
+                j,
+                bc,
+                k,
+                i,
+                k,
+                ac
+            );
+        }
+    }
+}
+
+// metricsフィーチャーが有効なとき、ソート1回ごとの統計をmetricsクレートのレコーダーに記録する
+// フィーチャーが無効なら、この関数も時間の計測もコンパイルされないので、オーバーヘッドはない
+#[cfg(feature = "metrics")]
+fn record_metrics(len: usize, metrics: &SortMetrics, duration: Duration) {
+    metrics::counter!("bitonic_sorter.sorts").increment(1);
+    metrics::histogram!("bitonic_sorter.elements").record(len as f64);
+    metrics::histogram!("bitonic_sorter.comparisons").record(metrics.comparisons as f64);
+    metrics::histogram!("bitonic_sorter.duration_seconds").record(duration.as_secs_f64());
+}
+
+// ソート済みの配列の並びをO(n)で反転する（昇順を降順に、降順を昇順にする）
+// ソートし直すとネットワーク全体を実行するので、向きを変えるだけならこちらを使う
+// ソートの前の確認（sort_presorted）で、入力が逆順に並んでいると分かったときにも使う
+// 比較が等しい要素どうしの順序も反転する
+pub fn reverse_in_place<T>(array: &mut [T]) {
+    array.reverse();
+}
+
+// ソートの前に、入力がすでにcomparatorの順に並んでいるか、その逆順に並んでいるかを確認する
+// comparatorの順なら何もせず、逆順なら反転するだけでソートを終えて、Some(metrics)を返す
+// 降順のソートではcomparatorが逆向きになるので、降順に並んだ入力なら何もせず、
+// 昇順に並んだ入力なら反転する（どちらの向きでも同じように扱われる）
+// ソートを終えたときは、確認に使った比較の回数をmetrics.comparisonsに数える
+// 確認はふつう先頭の数要素で打ち切られるので、ランダムな入力ではほとんど時間がかからない
+// そのときの数回の比較は数えずにNoneを返すので、ネットワークを実行したときの比較の回数は
+// seq::sort_by_countingやネットワークの比較の回数（analysis）と一致する
+// 要素数が2以下ならネットワークの比較も高々1回なので、確認せずにNoneを返す
+fn sort_presorted<T, F>(array: &mut [T], comparator: &F) -> Option<SortMetrics>
+where
+    F: Fn(&T, &T) -> Ordering,
+{
+    if array.len() <= 2 {
+        return None;
+    }
+    let (ascending, ascending_checks) = check_sorted_in_direction(array, true, comparator);
+    if ascending {
+        return Some(SortMetrics {
+            comparisons: ascending_checks,
+            ..SortMetrics::default()
+        });
+    }
+    let (descending, descending_checks) = check_sorted_in_direction(array, false, comparator);
+    if descending {
+        reverse_in_place(array);
+        Some(SortMetrics {
+            comparisons: ascending_checks + descending_checks,
+            swaps: array.len() as u64 / 2,
+            ..SortMetrics::default()
+        })
+    } else {
+        None
+    }
+}
+
+// 使えるCPUコア数と要素数から、並列に処理するかどうかを決めてソートする
+// 小さな配列やシングルコアの環境ではスレッドプールを一切使わないので、
+// タスクの受け渡しの遅延がソート時間を上回ることがない
+pub fn sort_adaptive<T: Ord + Send>(array: &mut [T], order: &SortOrder) -> Result<(), SortError> {
+    let cores = std::thread::available_parallelism().map_or(1, |n| n.get());
+    let threshold = adaptive_threshold::<T>(array.len(), cores);
+    match *order {
+        SortOrder::Ascending => sort_by_threshold_counting(array, &|a, b| a.cmp(b), threshold),
+        SortOrder::Descending => sort_by_threshold_counting(array, &|a, b| b.cmp(a), threshold),
+    }
+    .map(|_| ())
+}
+
+// 要素数lenとコア数coresに応じたしきい値を返す
+// 並列化しないときはusize::MAXを返す
+fn adaptive_threshold<T>(len: usize, cores: usize) -> usize {
+    let threshold = parallel_threshold::<T>();
+    // 最初の分割でもしきい値に届かないなら、並列化しない
+    if cores <= 1 || len / 2 < threshold {
+        usize::MAX
+    } else {
+        // コア数の数倍のタスクに分かれる程度まで分割すれば十分なので、
+        // それより細かくは分割しない
+        threshold.max(len / (cores * 4))
+    }
+}
+
+// ソートし、要素の交換が1回でも起きたならOk(true)を返す
+// すでにソート済みなら何もせず（交換0回で）Ok(false)を返す
+// バイトニックソートはソート済みの入力でも後半を逆順に並べ替えるが、
+// sort_by_countingがネットワークを実行する前にソート済みかどうかを確認している
+pub fn sort_by_reporting<T, F>(array: &mut [T], comparator: &F) -> Result<bool, SortError>
+where
+    T: Send,
+    F: Sync + Fn(&T, &T) -> Ordering,
+{
+    sort_by_counting(array, comparator).map(|metrics| metrics.swaps > 0)
+}
+
+// sort_outcomeで、ソートがどのように終わったか
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SortOutcome {
+    Empty,         // 要素がなかった
+    AlreadySorted, // すでにソート済みで、要素を動かさなかった
+    Sorted,        // 要素を並べ替えてソートした
+}
+
+// ソートし、その結果をSortOutcomeで返す
+// 要素数0の配列は2のべき乗ではないが、エラーにせずOk(SortOutcome::Empty)を返す
+// 要素が1つだけの配列は、ソート済みとして扱う
+pub fn sort_outcome<T: Ord + Send>(
+    array: &mut [T],
+    order: &SortOrder,
+) -> Result<SortOutcome, SortError> {
+    if array.is_empty() {
+        return Ok(SortOutcome::Empty);
+    }
+    let swapped = match *order {
+        SortOrder::Ascending => sort_by_reporting(array, &|a: &T, b: &T| a.cmp(b))?,
+        SortOrder::Descending => sort_by_reporting(array, &|a: &T, b: &T| b.cmp(a))?,
+    };
+    Ok(if swapped {
+        SortOutcome::Sorted
+    } else {
+        SortOutcome::AlreadySorted
+    })
+}
+
+// do_sortとsub_sortの動作を調整する設定
+#[derive(Debug, Clone, Copy)]
+struct Tuning {
+    // 部分配列の分割後の要素数がこれ以上なら並列に処理する
+    threshold: usize,
+    // sub_sortで、要素数がこれ以下のソート済みの部分配列の再帰を省く（0なら省かない）
+    skip_sorted_len: usize,
+}
+
+impl Tuning {
+    fn with_threshold(threshold: usize) -> Self {
+        Self {
+            threshold,
+            skip_sorted_len: 0,
+        }
+    }
+
+    fn without_skip(self) -> Self {
+        Self {
+            skip_sorted_len: 0,
+            ..self
+        }
+    }
+}
+
+// sub_sortでソート済みかを確認する部分配列の最大の要素数
+// 確認にかかる比較は高々len - 1回で、sub_sortの(len / 2) * log2(len)回より少ない
+#[cfg(test)]
+const SKIP_SORTED_MAX_LEN: usize = 64;
+
+// sort_byと同じ結果になるが、sub_sortの途中でソート済みになった小さな部分配列
+// （要素数SKIP_SORTED_MAX_LEN以下）の再帰を省く
+// ただしバイトニックソートは前半を昇順、後半を降順に並べ替えるので、ソート済みの入力でも
+// 途中の部分配列はソート済みになるとは限らない。0.1%の要素を入れ替えた2^20要素の入力では
+// 比較回数がかえって約1%増えた（確認のための比較が増える分が、省いた比較を上回る）
+// 効果が見込める入力が限られるので公開せず、テストでsub_sortの省略を確かめるのにだけ使う
+#[cfg(test)]
+pub(crate) fn sort_by_skipping_sorted<T, F>(
+    array: &mut [T],
+    comparator: &F,
+) -> Result<SortMetrics, SortError>
 where
     T: Send,
     F: Sync + Fn(&T, &T) -> Ordering,
 {
-    if array.len().is_power_of_two() {
-        do_sort(array, true, comparator);
-        Ok(())
+    require_power_of_two(array.len())?;
+    if let Some(metrics) = sort_presorted(array, comparator) {
+        return Ok(metrics);
+    }
+    let tuning = Tuning {
+        threshold: parallel_threshold::<T>(),
+        skip_sorted_len: SKIP_SORTED_MAX_LEN,
+    };
+    Ok(do_sort(array, true, comparator, tuning))
+}
+
+// arrayがis_ascで示される向きにソート済みかを確認する
+// ソート済みかどうかと、確認に使った比較の回数を返す
+fn check_sorted_in_direction<T, F>(array: &[T], is_asc: bool, comparator: &F) -> (bool, u64)
+where
+    F: Fn(&T, &T) -> Ordering,
+{
+    let out_of_order = if is_asc {
+        Ordering::Greater
     } else {
-        Err(format!(
-            "The length of x is not a power of two. (x.len(): {})",
-            array.len()
-        ))
+        Ordering::Less
+    };
+    let in_order = array
+        .windows(2)
+        .take_while(|pair| comparator(&pair[0], &pair[1]) != out_of_order)
+        .count();
+    let pairs = array.len().saturating_sub(1);
+    if in_order == pairs {
+        (true, pairs as u64)
+    } else {
+        (false, in_order as u64 + 1)
     }
 }
 
-fn do_sort<T, F>(array: &mut [T], is_asc: bool, comparator: &F)
+fn do_sort<T, F>(array: &mut [T], is_asc: bool, comparator: &F, tuning: Tuning) -> SortMetrics
 where
     T: Send,
     F: Sync + Fn(&T, &T) -> Ordering,
@@ -40,42 +494,65 @@ where
         // let first = &mut x[0..1];
         // let second = &mut x[2..3];
         let (first, second) = array.split_at_mut(mid_point);
-        if mid_point > PARALLEL_THRESHOLD {
+        let (first_metrics, second_metrics) = if mid_point > tuning.threshold {
             // しきい値以上なら並列にソートする（並列処理）
-            rayon::join(
-                || do_sort(first, true, comparator),
-                || do_sort(second, false, comparator),
+            let (first_metrics, second_metrics) = rayon::join(
+                || do_sort(first, true, comparator, tuning),
+                || do_sort(second, false, comparator, tuning),
             );
+            (first_metrics.with_parallel_task(), second_metrics)
         } else {
-            do_sort(first, true, comparator);
-            do_sort(second, false, comparator);
-        }
-        sub_sort(array, is_asc, comparator);
+            (
+                do_sort(first, true, comparator, tuning),
+                do_sort(second, false, comparator, tuning),
+            )
+        };
+        first_metrics + second_metrics + sub_sort(array, is_asc, comparator, tuning)
+    } else {
+        SortMetrics::default()
     }
 }
 
-fn sub_sort<T, F>(array: &mut [T], is_asc: bool, comparator: &F)
+fn sub_sort<T, F>(array: &mut [T], is_asc: bool, comparator: &F, tuning: Tuning) -> SortMetrics
 where
     T: Send,
     F: Sync + Fn(&T, &T) -> Ordering,
 {
+    if array.len() <= tuning.skip_sorted_len {
+        // すでにソート済みの部分配列はsub_sortしても並びが変わらないので、再帰を省く
+        let (is_sorted, checks) = check_sorted_in_direction(array, is_asc, comparator);
+        let check_metrics = SortMetrics {
+            comparisons: checks,
+            ..SortMetrics::default()
+        };
+        if is_sorted {
+            return check_metrics;
+        }
+        return check_metrics + sub_sort(array, is_asc, comparator, tuning.without_skip());
+    }
     if array.len() > 1 {
-        compare_and_swap(array, is_asc, comparator);
+        let metrics = compare_and_swap(array, is_asc, comparator);
         let mid_point = array.len() / 2;
         let (first, second) = array.split_at_mut(mid_point);
-        if mid_point >= PARALLEL_THRESHOLD {
-            rayon::join(
-                || sub_sort(first, is_asc, comparator),
-                || sub_sort(second, is_asc, comparator),
+        let (first_metrics, second_metrics) = if mid_point >= tuning.threshold {
+            let (first_metrics, second_metrics) = rayon::join(
+                || sub_sort(first, is_asc, comparator, tuning),
+                || sub_sort(second, is_asc, comparator, tuning),
             );
+            (first_metrics.with_parallel_task(), second_metrics)
         } else {
-            sub_sort(first, is_asc, comparator);
-            sub_sort(second, is_asc, comparator);
-        }
+            (
+                sub_sort(first, is_asc, comparator, tuning),
+                sub_sort(second, is_asc, comparator, tuning),
+            )
+        };
+        metrics + first_metrics + second_metrics
+    } else {
+        SortMetrics::default()
     }
 }
 
-fn compare_and_swap<T, F>(array: &mut [T], is_asc: bool, comparator: &F)
+fn compare_and_swap<T, F>(array: &mut [T], is_asc: bool, comparator: &F) -> SortMetrics
 where
     F: Fn(&T, &T) -> Ordering,
 {
@@ -86,194 +563,4024 @@ where
         Ordering::Less
     };
     let mid_point = array.len() / 2;
+    let mut swaps = 0;
     for i in 0..mid_point {
         // comparatorクロージャで2要素を比較し、返されたOrderingのバリアントが
         // swap_conditionと等しいなら要素を交換する
         if comparator(&array[i], &array[mid_point + i]) == swap_condition {
             array.swap(i, mid_point + i);
+            swaps += 1;
         }
     }
+    SortMetrics {
+        comparisons: mid_point as u64,
+        swaps,
+        parallel_tasks: 0,
+    }
 }
 
-#[cfg(test)]
-mod tests {
-    use std::time::Instant;
+// predicateがfalseを返す要素を前に、trueを返す要素を後ろに移動する
+// 値が2種類しかないキーでのソートに相当するが、バイトニックソートより高速に動作する
+// 各グループ内の要素の順序は保たれる（安定な分割）
+// 戻り値はfalseのグループの要素数（trueのグループが始まる位置）
+// バイトニックソートと違い、要素数は2のべき乗でなくてもよい
+pub fn partition_by<T, F>(array: &mut [T], predicate: &F) -> usize
+where
+    T: Send,
+    F: Sync + Fn(&T) -> bool,
+{
+    if array.len() <= 1 {
+        return match array.first() {
+            Some(value) if !predicate(value) => 1,
+            _ => 0,
+        };
+    }
+    let mid_point = array.len() / 2;
+    let (first, second) = array.split_at_mut(mid_point);
+    let (first_falses, second_falses) = if mid_point >= PARALLEL_THRESHOLD {
+        rayon::join(
+            || partition_by(first, predicate),
+            || partition_by(second, predicate),
+        )
+    } else {
+        (
+            partition_by(first, predicate),
+            partition_by(second, predicate),
+        )
+    };
+    // [F1 T1 | F2 T2] の T1 F2 の部分を回転させて [F1 F2 | T1 T2] にする
+    array[first_falses..mid_point + second_falses].rotate_left(mid_point - first_falses);
+    first_falses + second_falses
+}
 
-    use super::{sort, sort_by};
-    use crate::utils::{is_sorted_ascending, is_sorted_descending, new_u32_vec};
-    use crate::SortOrder::*;
+// classifyがLessを返す要素、Equalを返す要素、Greaterを返す要素の順に並べる（3つに分割する）
+// 戻り値の(less, greater)は、Equalのグループが始まる位置と、Greaterのグループが始まる位置
+// つまりarray[..less]がLess、array[less..greater]がEqual、array[greater..]がGreaterになる
+// ピボットとの比較をclassifyに渡せば、クイックセレクトの分割に使える
+// partition_byを2回使うので、大きな配列では並列に処理し、各グループ内の要素の順序は保たれる
+// 要素数は2のべき乗でなくてもよい
+pub fn three_way_partition_by<T, F>(array: &mut [T], classify: &F) -> (usize, usize)
+where
+    T: Send,
+    F: Sync + Fn(&T) -> Ordering,
+{
+    let less = partition_by(array, &|v| classify(v) != Ordering::Less);
+    let equal = partition_by(&mut array[less..], &|v| classify(v) == Ordering::Greater);
+    (less, less + equal)
+}
 
-    // 構造体Studentを定義する
-    // 構造体は関連する値を1つにまとめたデータ構造。複数のデータフィールドを持つ
+/// FFIなどから受け取った、未初期化のまま確保されて後から値が書き込まれる
+/// バッファを、コピーせずにその場でソートする
+/// 先頭から`initialized_len`個の要素をソートの対象とする
+///
+/// # Safety
+///
+/// 呼び出し側は`array`の先頭`initialized_len`個の要素がすべて有効な`T`の値で
+/// 初期化済みであることを保証しなければならない
+/// `initialized_len`が`array.len()`より大きいときはパニックする
+pub unsafe fn sort_maybe_uninit<T: Ord + Send>(
+    array: &mut [MaybeUninit<T>],
+    initialized_len: usize,
+    order: &SortOrder,
+) -> Result<(), SortError> {
+    assert!(initialized_len <= array.len());
+    // MaybeUninit<T>はTと同じメモリレイアウトを持つので、初期化済みの範囲は
+    // &mut [T]として扱える
+    let initialized = std::slice::from_raw_parts_mut(array.as_mut_ptr() as *mut T, initialized_len);
+    sort(initialized, order)
+}
 
-    // deriveアトリビュートを使い、DebugトレイトとPartialEqトレイトの実装を自動導出する
-    #[derive(Debug, PartialEq)]
-    struct Student {
-        first_name: String, // first_name（名前）フィールド。String型
-        last_name: String,  // last_name（苗字）フィールド。String型
-        age: u8,            // age（年齢）フィールド。u8型（8ビット符号なし整数）
+// すでにソート済みならコピーせずに借用を返し、そうでなければ複製してからソートする
+// ソート済みのデータが多いときに、不要なメモリ確保を避けられる
+pub fn sort_cow<'a, T>(array: &'a [T], order: &SortOrder) -> Result<Cow<'a, [T]>, SortError>
+where
+    T: Ord + Clone + Send,
+{
+    let is_sorted = match *order {
+        SortOrder::Ascending => is_sorted_ascending(array),
+        SortOrder::Descending => is_sorted_descending(array),
+    };
+    if is_sorted {
+        Ok(Cow::Borrowed(array))
+    } else {
+        let mut owned = array.to_vec();
+        sort(&mut owned, order)?;
+        Ok(Cow::Owned(owned))
     }
+}
 
-    // implブロックを使うと対象の型に関連関数やメソッドを実装できる
-    impl Student {
-        // 関連関数newを定義する
-        fn new(first_name: &str, last_name: &str, age: u8) -> Self {
-            // 構造体Studentを初期化して返す。Selfはimpl対象の型（Student）の別名
-            Self {
-                // to_stringメソッドで&str型の引数からString型の値を作る。詳しくは5章で説明
-                first_name: first_name.to_string(), // first_nameフィールドに値を設定
-                last_name: last_name.to_string(),   // last_nameフィールドに値を設定
-                age,                                // ageフィールドにage変数の値を設定
-                                                    // フィールドと変数が同じ名前のときは、このように省略形で書ける
-            }
+// 参照のスライス（例：Vec<&Student>）を、参照先の値を比較してソートする
+// 入れ替わるのは参照（ポインタ）だけで、参照先の値は移動しない
+// 参照をスレッド間で受け渡すので、参照先の型TはSyncでなければならない
+// （&TがSendになるのはTがSyncのときだけ）
+// 'aは参照先の値が生存している期間で、ソートの前後で参照の寿命は変わらない
+pub fn sort_refs_by<'a, T, F>(array: &mut [&'a T], comparator: &F) -> Result<(), SortError>
+where
+    T: Sync,
+    F: Sync + Fn(&T, &T) -> Ordering,
+{
+    sort_by(array, &|a: &&'a T, b: &&'a T| comparator(a, b))
+}
+
+// keyで取り出したキーの順にソートする
+// キーは最初に一度だけ取り出してバッファに格納しておくので、key関数の呼び出しは
+// 要素1つにつき1回で済み、Copyでないキー（Stringなど）やstd::cmp::Reverseのような
+// Ordを実装したラッパ型もそのままキーとして使える
+// キーが等しい要素は元の順序が保たれる（安定ソート）
+pub fn sort_keys<T, K, F>(array: &mut [T], key: &F) -> Result<(), SortError>
+where
+    T: Send + Sync,
+    K: Ord + Send,
+    F: Sync + Fn(&T) -> K,
+{
+    let mut permutation = key_permutation(array, key)?;
+    apply_permutation(array, &mut permutation);
+    Ok(())
+}
+
+// sort_keysと同じだが、要素がCopyなら交換の代わりにコピーで並べ替える
+// 並べ替えでの要素の書き込みが、交換（1回につき2要素）のほぼ半分で済む
+pub fn sort_keys_copied<T, K, F>(array: &mut [T], key: &F) -> Result<(), SortError>
+where
+    T: Copy + Send + Sync,
+    K: Ord + Send,
+    F: Sync + Fn(&T) -> K,
+{
+    let mut permutation = key_permutation(array, key)?;
+    apply_permutation_in_place(array, &mut permutation);
+    Ok(())
+}
+
+// キーの順に並べたときに、i番目に来る要素の元の位置を並べた配列を返す
+fn key_permutation<T, K, F>(array: &[T], key: &F) -> Result<Vec<usize>, SortError>
+where
+    T: Sync,
+    K: Ord + Send,
+    F: Sync + Fn(&T) -> K,
+{
+    require_power_of_two(array.len())?;
+    // キーと元の位置の組を作る。キーが等しいときは位置で比較するので、ソートの結果が安定になる
+    // キーは並列に取り出すが、enumerateの位置は取り出す順序によらず元の位置になる
+    let mut keyed: Vec<(K, usize)> = array
+        .par_iter()
+        .enumerate()
+        .map(|(i, value)| (key(value), i))
+        .collect();
+    // 位置による比較がキーの比較の後に必ず行われるように、組の比較を明示的に書く
+    sort_by(&mut keyed, &|a: &(K, usize), b: &(K, usize)| {
+        a.0.cmp(&b.0).then(a.1.cmp(&b.1))
+    })?;
+    Ok(keyed.into_iter().map(|(_, i)| i).collect())
+}
+
+// 並べ替え後のi番目の要素が元のpermutation[i]番目の要素になるように、
+// arrayをその場で並べ替える
+// 巡回置換をたどりながら要素を交換し、処理済みの位置はpermutation[i] = iにして印を付ける
+pub(crate) fn apply_permutation<T>(array: &mut [T], permutation: &mut [usize]) {
+    for start in 0..array.len() {
+        let mut current = start;
+        while permutation[current] != start {
+            let next = permutation[current];
+            array.swap(current, next);
+            permutation[current] = current;
+            current = next;
         }
+        permutation[current] = current;
     }
+}
 
-    #[test]
-    fn sort_to_fail() {
-        let mut x = vec![10, 30, 11]; // x.len() が2のべき乗になっていない。
-        assert!(sort(&mut x, &Ascending).is_err());
+// apply_permutationと同じようにarrayを並べ替えるが、要素をコピーで移動する
+// 巡回置換ごとに先頭の要素だけを退避し、残りの要素は1つずつ前に詰めていくので、
+// 各要素の書き込みは1回で済む。permutationの使い方（処理済みの印）もapply_permutationと同じ
+pub fn apply_permutation_in_place<T: Copy>(array: &mut [T], permutation: &mut [usize]) {
+    assert_eq!(
+        array.len(),
+        permutation.len(),
+        "the permutation must have the same length as the array"
+    );
+    for start in 0..array.len() {
+        if permutation[start] == start {
+            continue;
+        }
+        let first = array[start];
+        let mut current = start;
+        while permutation[current] != start {
+            let next = permutation[current];
+            array[current] = array[next];
+            permutation[current] = current;
+            current = next;
+        }
+        array[current] = first;
+        permutation[current] = current;
     }
+}
 
-    #[test]
-    fn sort_u32_ascending() {
-        let mut x: Vec<u32> = vec![10, 30, 11, 20, 4, 330, 21, 110];
-        assert_eq!(sort(&mut x, &Ascending), Ok(()));
-        assert_eq!(x, vec![4, 10, 11, 20, 21, 30, 110, 330]);
+// イテレータから値を集めてソートし、ソート済みのベクタを返す
+// 要素数が2のべき乗でなくてもよい（内部で2のべき乗まで埋めてから取り除く）
+// 値を集めるベクタと、埋めるための作業用のベクタの分のメモリを確保する
+pub fn sorted_from<T, I>(iter: I, order: &SortOrder) -> Vec<T>
+where
+    T: Ord + Send,
+    I: IntoIterator<Item = T>,
+{
+    let values: Vec<T> = iter.into_iter().collect();
+    match *order {
+        SortOrder::Ascending => sort_padded_by(values, &|a, b| a.cmp(b)),
+        SortOrder::Descending => sort_padded_by(values, &|a, b| b.cmp(a)),
     }
+}
 
-    #[test]
-    fn sort_u32_descending() {
-        let mut x: Vec<u32> = vec![10, 30, 11, 20, 4, 330, 21, 110];
-        assert_eq!(sort(&mut x, &Descending), Ok(()));
-        assert_eq!(x, vec![330, 110, 30, 21, 20, 11, 10, 4]);
+// 要素数が2のべき乗になるまでNoneで埋めてからソートし、Noneを取り除いて返す
+// Noneはどの値よりも後ろに並ぶように比較するので、ソート後は末尾に集まる
+pub(crate) fn sort_padded_by<T, F>(values: Vec<T>, comparator: &F) -> Vec<T>
+where
+    T: Send,
+    F: Sync + Fn(&T, &T) -> Ordering,
+{
+    let len = values.len();
+    let mut padded: Vec<Option<T>> = values.into_iter().map(Some).collect();
+    padded.resize_with(len.next_power_of_two(), || None);
+    sort_by(&mut padded, &|a, b| match (a, b) {
+        (Some(a), Some(b)) => comparator(a, b),
+        (Some(_), None) => Ordering::Less,
+        (None, Some(_)) => Ordering::Greater,
+        (None, None) => Ordering::Equal,
+    })
+    .expect("the padded length must be a power of two");
+    padded.into_iter().flatten().collect()
+}
+
+// keyで取り出したキーの順にソートし、キーが等しい要素のグループごとに
+// その添字の範囲を返す。集計処理などでグループ単位に処理するときに使う
+// ソートはsort_keysで行うので、グループ内の要素は元の順序のまま並ぶ（安定ソート）
+pub fn sort_and_group_by<T, K, F>(array: &mut [T], key: &F) -> Result<Vec<Range<usize>>, SortError>
+where
+    T: Send + Sync,
+    K: Ord + Send,
+    F: Sync + Fn(&T) -> K,
+{
+    sort_keys(array, key)?;
+
+    let mut groups = Vec::new();
+    let mut start = 0;
+    for i in 1..=array.len() {
+        // 末尾に達したか、キーが変わったらグループを閉じる
+        if i == array.len() || key(&array[i - 1]) != key(&array[i]) {
+            groups.push(start..i);
+            start = i;
+        }
     }
+    Ok(groups)
+}
 
-    #[test]
-    fn sort_u32_large() {
-        {
-            // 乱数で65,536要素のデータ列を作る（65,536は2の16乗）
-            let mut x = new_u32_vec(65536*2*2*2*2*2*2*2);
-            let now = Instant::now();
-            // 昇順にソートする
-            assert_eq!(sort(&mut x, &Ascending), Ok(()));
-            println!("sorted: {:?}", now.elapsed());
-            // ソート結果が正しいことを検証する
-            assert!(is_sorted_ascending(&x));
+// comparatorの順にソートし、comparatorがEqualを返す隣り合った要素が続く区間（ラン）ごとに
+// その添字の範囲を返す。範囲は重ならず、順に並べると0..array.len()全体を覆う
+// 重複を取り除く処理などで、ソートと等しい要素の区切りを一度に求めるときに使う
+// ランの中の要素の順序は保たれない（安定ソートではない）
+pub fn sort_and_runs_by<T, F>(
+    array: &mut [T],
+    comparator: &F,
+) -> Result<Vec<Range<usize>>, SortError>
+where
+    T: Send,
+    F: Sync + Fn(&T, &T) -> Ordering,
+{
+    sort_by(array, comparator)?;
+
+    let mut runs = Vec::new();
+    let mut start = 0;
+    for i in 1..=array.len() {
+        // 末尾に達したか、隣の要素と等しくなくなったらランを閉じる
+        if i == array.len() || comparator(&array[i - 1], &array[i]) != Ordering::Equal {
+            runs.push(start..i);
+            start = i;
         }
-        // {
-        //     let mut x = new_u32_vec(65536);
-        //     assert_eq!(sort(&mut x, &Descending), Ok(()));
-        //     assert!(is_sorted_descending(&x));
-        // }
     }
+    Ok(runs)
+}
 
-    #[test]
-    fn sort_str_ascending() {
-        let mut x = vec![
-            "Rust",
-            "is",
-            "fast",
-            "and",
+// ソートし、かかった時間（実時間）を返す
+// ベンチマークのコードで時間の計測を毎回書かなくて済むようにする
+pub fn sort_by_timed<T, F>(array: &mut [T], comparator: &F) -> Result<Duration, SortError>
+where
+    T: Send,
+    F: Sync + Fn(&T, &T) -> Ordering,
+{
+    let start = Instant::now();
+    sort_by(array, comparator)?;
+    Ok(start.elapsed())
+}
+
+// sort_by_benchmarkedが返す、ソート1回の計測結果
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct BenchReport {
+    pub elapsed: Duration,     // かかった時間（実時間）
+    pub n: usize,              // ソートした要素数
+    pub elements_per_sec: f64, // 1秒あたりにソートした要素数（n / elapsed）
+}
+
+// ソートし、かかった時間と1秒あたりの要素数をBenchReportで返す
+// ベンチマークのツールどうしで、同じ計算方法のスループットを表示できるようにする
+// 時間が短すぎて計測できなかった（0になった）ときは、elements_per_secが無限大になる
+pub fn sort_by_benchmarked<T, F>(array: &mut [T], comparator: &F) -> Result<BenchReport, SortError>
+where
+    T: Send,
+    F: Sync + Fn(&T, &T) -> Ordering,
+{
+    let elapsed = sort_by_timed(array, comparator)?;
+    let n = array.len();
+    Ok(BenchReport {
+        elapsed,
+        n,
+        elements_per_sec: n as f64 / elapsed.as_secs_f64(),
+    })
+}
+
+// arrayの複製を順次処理（seq::sort）と並列処理（sort）でそれぞれソートし、
+// かかった時間を(順次処理, 並列処理)の組で返す。arrayはソートした結果で置き換える
+// 手元のデータで並列処理が速くなるかどうかを、手軽に確かめるときに使う
+// sortはソート済みや逆順の入力を見分ける事前の確認を行うが、seq::sortは行わないので、
+// そのような入力では並列処理の方が大幅に速く見える
+pub fn compare_backends<T>(
+    array: &mut [T],
+    order: &SortOrder,
+) -> Result<(Duration, Duration), SortError>
+where
+    T: Ord + Clone + Send,
+{
+    let mut sequential = array.to_vec();
+    let start = Instant::now();
+    seq::sort(&mut sequential, order)?;
+    let sequential_elapsed = start.elapsed();
+
+    let mut parallel = array.to_vec();
+    let start = Instant::now();
+    sort(&mut parallel, order)?;
+    let parallel_elapsed = start.elapsed();
+
+    array.clone_from_slice(&parallel);
+    Ok((sequential_elapsed, parallel_elapsed))
+}
+
+// primaryで比較し、等しい（Equal）ときだけsecondaryで比較してソートする
+// then_withで比較関数をつなげるのと同じ
+pub fn sort_by_two<T, F, G>(array: &mut [T], primary: &F, secondary: &G) -> Result<(), SortError>
+where
+    T: Send,
+    F: Sync + Fn(&T, &T) -> Ordering,
+    G: Sync + Fn(&T, &T) -> Ordering,
+{
+    sort_by(array, &|a, b| primary(a, b).then_with(|| secondary(a, b)))
+}
+
+// 入力に含まれる、すでに昇順（または降順）に並んだ区間（ラン）を見つけて利用するソート
+// 降順のランは反転してから、すべてのランをマージする（Timsortの考え方に近い）
+// ほぼソート済みのデータではランが少ないので、バイトニックソートより少ない比較で済む
+// ランの検出とマージのためにベクタを作り直すので、引数は&mut Vec<T>をとる
+// 要素数は2のべき乗でなくてもよい
+pub fn sort_adaptive_runs<T: Ord + Send>(array: &mut Vec<T>, order: &SortOrder) {
+    let values = std::mem::take(array);
+    *array = match *order {
+        SortOrder::Ascending => sort_runs_by(values, &|a, b| a.cmp(b)),
+        SortOrder::Descending => sort_runs_by(values, &|a, b| b.cmp(a)),
+    };
+}
+
+fn sort_runs_by<T, F>(mut values: Vec<T>, comparator: &F) -> Vec<T>
+where
+    T: Send,
+    F: Sync + Fn(&T, &T) -> Ordering,
+{
+    // ランの境界を求める。厳密に降順のランはその場で反転しておく
+    let mut bounds = Vec::new();
+    let mut start = 0;
+    while start < values.len() {
+        let mut end = start + 1;
+        if end < values.len() && comparator(&values[start], &values[end]) == Ordering::Greater {
+            while end < values.len()
+                && comparator(&values[end - 1], &values[end]) == Ordering::Greater
+            {
+                end += 1;
+            }
+            values[start..end].reverse();
+        } else {
+            while end < values.len()
+                && comparator(&values[end - 1], &values[end]) != Ordering::Greater
+            {
+                end += 1;
+            }
+        }
+        bounds.push(end);
+        start = end;
+    }
+
+    // 後ろから切り出して、ランごとのベクタに分ける
+    let mut runs = Vec::with_capacity(bounds.len());
+    for &bound in bounds.iter().rev().skip(1) {
+        runs.push(values.split_off(bound));
+    }
+    runs.push(values);
+    runs.reverse();
+    merge_sorted_runs(runs, comparator)
+}
+
+// ソート済みのベクタの列をマージして1つのソート済みのベクタにする（k-wayマージ）
+// ランの列を半分に分けてそれぞれを（要素数がしきい値以上なら並列に）マージし、
+// 最後に2つをマージする
+fn merge_sorted_runs<T, F>(mut runs: Vec<Vec<T>>, comparator: &F) -> Vec<T>
+where
+    T: Send,
+    F: Sync + Fn(&T, &T) -> Ordering,
+{
+    match runs.len() {
+        0 => Vec::new(),
+        1 => runs.pop().unwrap(),
+        len => {
+            let total: usize = runs.iter().map(Vec::len).sum();
+            let second_runs = runs.split_off(len / 2);
+            let (first, second) = if total >= PARALLEL_THRESHOLD {
+                rayon::join(
+                    || merge_sorted_runs(runs, comparator),
+                    || merge_sorted_runs(second_runs, comparator),
+                )
+            } else {
+                (
+                    merge_sorted_runs(runs, comparator),
+                    merge_sorted_runs(second_runs, comparator),
+                )
+            };
+            merge_two(first, second, comparator)
+        }
+    }
+}
+
+// 2つのソート済みのベクタをマージする。等しい要素はfirstの側を先に置く
+pub(crate) fn merge_two<T, F>(first: Vec<T>, second: Vec<T>, comparator: &F) -> Vec<T>
+where
+    F: Fn(&T, &T) -> Ordering,
+{
+    let mut merged = Vec::with_capacity(first.len() + second.len());
+    let mut first = first.into_iter().peekable();
+    let mut second = second.into_iter().peekable();
+    loop {
+        let take_first = match (first.peek(), second.peek()) {
+            (Some(a), Some(b)) => comparator(a, b) != Ordering::Greater,
+            (Some(_), None) => true,
+            (None, Some(_)) => false,
+            (None, None) => break,
+        };
+        if take_first {
+            merged.extend(first.next());
+        } else {
+            merged.extend(second.next());
+        }
+    }
+    merged
+}
+
+// 再帰を使わずに、段（stage）ごとのループでバイトニックソートを行う
+// 要素数2^kの配列は、大きさ2, 4, ..., 2^kのブロックを順にバイトニックマージするk段で
+// ソートできる。段数kはlog2_exactで求め、ループの回数の上限にも使う
+// 結果はsort_byと同じになる
+pub fn sort_by_iterative<T, F>(array: &mut [T], comparator: &F) -> Result<SortMetrics, SortError>
+where
+    T: Send,
+    F: Sync + Fn(&T, &T) -> Ordering,
+{
+    let stages = log2_exact(array.len()).ok_or_else(|| SortError::not_power_of_two(array.len()))?;
+    let parallel = array.len() >= parallel_threshold::<T>();
+    let mut metrics = SortMetrics::default();
+    for stage in 1..=stages {
+        metrics = metrics + bitonic_stage(array, stage, stages, comparator, parallel);
+    }
+    Ok(metrics)
+}
+
+// sort_by_iterativeと同じ段ごとの処理でソートし、各段にかかった時間（実時間）を返す
+// 第i段（i = 1, 2, ..., log2(n)）は大きさ2^iのブロックをすべてマージし終えるまでの時間で、
+// 段の中のブロックは並列に処理されるので、並列に処理された全体の経過時間になる
+// 戻り値のベクタの長さは段数log2(n)に等しい。エラーのときは空になる
+pub fn sort_by_stage_timings<T, F>(
+    array: &mut [T],
+    comparator: &F,
+) -> (Result<(), SortError>, Vec<Duration>)
+where
+    T: Send,
+    F: Sync + Fn(&T, &T) -> Ordering,
+{
+    let stages = match require_power_of_two(array.len()) {
+        Ok(stages) => stages,
+        Err(err) => return (Err(err), Vec::new()),
+    };
+    let parallel = array.len() >= parallel_threshold::<T>();
+    let timings = (1..=stages)
+        .map(|stage| {
+            let start = Instant::now();
+            bitonic_stage(array, stage, stages, comparator, parallel);
+            start.elapsed()
+        })
+        .collect();
+    (Ok(()), timings)
+}
+
+// sort_by_with_stage_statsで、各段の終わりに渡す統計
+// comparisonsとswapsは、最初の段からその段までの累計
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct StageStats {
+    pub stage: usize, // 終わった段（1からlog2(n)まで）
+    pub comparisons: u64,
+    pub swaps: u64,
+}
+
+// sort_by_iterativeと同じ段ごとの処理でソートし、段が終わるたびにon_stageを呼ぶ
+// on_stageはこの関数を呼んだスレッドで、段の中の並列な処理がすべて終わってから順に呼ぶ
+// 同時に2回以上呼ばれることはないが、呼び出し元のスレッドプールの中で動くことがあるので、
+// on_stageにはSyncを求める。on_stageの処理が長いと、その間は次の段に進まない
+pub fn sort_by_with_stage_stats<T, F, C>(
+    array: &mut [T],
+    comparator: &F,
+    on_stage: C,
+) -> Result<(), SortError>
+where
+    T: Send,
+    F: Sync + Fn(&T, &T) -> Ordering,
+    C: Fn(StageStats) + Sync,
+{
+    let stages = require_power_of_two(array.len())?;
+    let parallel = array.len() >= parallel_threshold::<T>();
+    let mut metrics = SortMetrics::default();
+    for stage in 1..=stages {
+        metrics = metrics + bitonic_stage(array, stage, stages, comparator, parallel);
+        on_stage(StageStats {
+            stage: stage as usize,
+            comparisons: metrics.comparisons,
+            swaps: metrics.swaps,
+        });
+    }
+    Ok(())
+}
+
+// 第stage段の処理。大きさ2^stageのブロックごとに、バイトニック列をマージする
+// 最後の段以外では、偶数番目のブロックを昇順、奇数番目のブロックを降順にそろえる
+// （再帰版のdo_sortが前半をtrue、後半をfalseでソートするのと同じ）
+fn bitonic_stage<T, F>(
+    array: &mut [T],
+    stage: u32,
+    stages: u32,
+    comparator: &F,
+    parallel: bool,
+) -> SortMetrics
+where
+    T: Send,
+    F: Sync + Fn(&T, &T) -> Ordering,
+{
+    let block_len = 1 << stage;
+    let merge_block = |(index, block): (usize, &mut [T])| {
+        let is_asc = stage == stages || index % 2 == 0;
+        let mut metrics = SortMetrics::default();
+        // sub_sortの再帰をループに展開したもの
+        let mut half = block_len / 2;
+        while half >= 1 {
+            for chunk in block.chunks_mut(half * 2) {
+                metrics = metrics + compare_and_swap(chunk, is_asc, comparator);
+            }
+            half /= 2;
+        }
+        metrics
+    };
+    if parallel {
+        array
+            .par_chunks_mut(block_len)
+            .enumerate()
+            .map(merge_block)
+            .reduce(SortMetrics::default, |a, b| a + b)
+    } else {
+        array
+            .chunks_mut(block_len)
+            .enumerate()
+            .map(merge_block)
+            .fold(SortMetrics::default(), |a, b| a + b)
+    }
+}
+
+// Arcで共有された要素のスライス（例：Vec<Arc<Node>>）を、参照先の値を比較してソートする
+// Arc<T>はTがOrdならOrdだが、フィールドで比較したいときはcomparatorを渡す
+// 入れ替わるのはArc（ポインタ）だけで、参照カウントも変化しない
+// Arc<T>がSendになるのはTがSend + Syncのときなので、Tにはその両方が必要になる
+// （Rc<T>はSendではないので、このソートには使えない）
+pub fn sort_arc_by<T, F>(array: &mut [Arc<T>], comparator: &F) -> Result<(), SortError>
+where
+    T: Send + Sync,
+    F: Sync + Fn(&T, &T) -> Ordering,
+{
+    sort_by(array, &|a: &Arc<T>, b: &Arc<T>| comparator(a, b))
+}
+
+// 要素数Nがコンパイル時に決まる配列[T; N]をソートする
+// Nが2のべき乗でなければコンパイルエラーになるので、実行時のエラー処理は要らない
+// Nが4以下なら、比較の回数が最小になるように組んだ小さなソーティングネットワークを使う
+pub fn sort_array<T: Ord + Send, const N: usize>(array: &mut [T; N], order: &SortOrder) {
+    #[allow(clippy::let_unit_value)]
+    let () = AssertPowerOfTwo::<N>::OK;
+    match *order {
+        SortOrder::Ascending => sort_array_by(array, &|a, b| a.cmp(b)),
+        SortOrder::Descending => sort_array_by(array, &|a, b| b.cmp(a)),
+    }
+}
+
+fn sort_array_by<T, F, const N: usize>(array: &mut [T; N], comparator: &F)
+where
+    T: Send,
+    F: Sync + Fn(&T, &T) -> Ordering,
+{
+    let mut compare_exchange = |i: usize, j: usize| {
+        compare_and_swap_at(&mut array[..], i, j, comparator);
+    };
+    match N {
+        1 => {}
+        2 => compare_exchange(0, 1),
+        4 => {
+            // 要素数4の最適なネットワーク（比較5回。バイトニックソートは6回）
+            compare_exchange(0, 1);
+            compare_exchange(2, 3);
+            compare_exchange(0, 2);
+            compare_exchange(1, 3);
+            compare_exchange(1, 2);
+        }
+        _ => {
+            do_sort(
+                &mut array[..],
+                true,
+                comparator,
+                Tuning::with_threshold(parallel_threshold::<T>()),
+            );
+        }
+    }
+}
+
+// Nが2のべき乗であることをコンパイル時に検査する
+// sort_arrayの中でOKを参照すると定数が評価され、条件を満たさなければコンパイルエラーになる
+struct AssertPowerOfTwo<const N: usize>;
+
+impl<const N: usize> AssertPowerOfTwo<N> {
+    const OK: () = assert!(
+        N.is_power_of_two(),
+        "the length of the array must be a power of two"
+    );
+}
+
+// 位置iとjの要素を比較し、comparatorの順で後ろになるべき要素がiにあれば入れ替える
+// 入れ替えたときはtrueを返す。これを並べれば独自のソーティングネットワークを組める
+// （正しくソートできるかはanalysis::is_sorting_networkで確かめられる）
+pub fn compare_and_swap_at<T, F>(array: &mut [T], i: usize, j: usize, comparator: &F) -> bool
+where
+    F: Fn(&T, &T) -> Ordering,
+{
+    if comparator(&array[i], &array[j]) == Ordering::Greater {
+        array.swap(i, j);
+        true
+    } else {
+        false
+    }
+}
+
+// SortOrderの代わりにbool値で向きを指定するsort（descendingがtrueなら降順）
+pub fn sort_dir<T: Ord + Send>(array: &mut [T], descending: bool) -> Result<(), SortError> {
+    let order = if descending {
+        SortOrder::Descending
+    } else {
+        SortOrder::Ascending
+    };
+    sort(array, &order)
+}
+
+// comparatorの順でソートする。descendingがtrueならその逆順にする
+pub fn sort_by_dir<T, F>(array: &mut [T], comparator: &F, descending: bool) -> Result<(), SortError>
+where
+    T: Send,
+    F: Sync + Fn(&T, &T) -> Ordering,
+{
+    if descending {
+        sort_by(array, &|a, b| comparator(b, a))
+    } else {
+        sort_by(array, comparator)
+    }
+}
+
+// keysをcomparatorの順にソートし、それと同じ並べ替えをvaluesにも適用する
+// データベースの列のように、添字で対応付けられた2つの配列を一緒にソートするときに使う
+// keysとvaluesの要素数は等しくなければならない
+// キーが等しい要素は元の順序が保たれる（安定ソート）
+pub fn sort_paired_by<K, V, F>(
+    keys: &mut [K],
+    values: &mut [V],
+    comparator: &F,
+) -> Result<(), SortError>
+where
+    K: Send,
+    V: Send,
+    F: Sync + Fn(&K, &K) -> Ordering,
+{
+    let mut permutation = paired_permutation(keys, values.len(), comparator)?;
+    let mut permutation_copy = permutation.clone();
+    apply_permutation(keys, &mut permutation);
+    apply_permutation(values, &mut permutation_copy);
+    Ok(())
+}
+
+// sort_paired_byと同じ結果になるが、payloadsの要素を交換ではなく移動で並べ替える
+// 交換では巡回置換の長さLに対して2(L - 1)回の要素の移動が起きるが、移動ならL + 1回で済み、
+// 各要素は1回だけ書き込まれる。大きな構造体を別の配列（SoA: struct of arrays）に
+// 持っているときに、ペイロードの移動を減らすために使う（examples/soa_benchmark.rs）
+pub fn sort_soa_by<K, V, F>(
+    keys: &mut [K],
+    payloads: &mut [V],
+    comparator: &F,
+) -> Result<(), SortError>
+where
+    K: Send,
+    V: Send,
+    F: Sync + Fn(&K, &K) -> Ordering,
+{
+    let mut permutation = paired_permutation(keys, payloads.len(), comparator)?;
+    let mut permutation_copy = permutation.clone();
+    apply_permutation(keys, &mut permutation);
+    apply_permutation_by_moving(payloads, &mut permutation_copy);
+    Ok(())
+}
+
+// keysをcomparatorの順に並べたときに、i番目に来る要素の元の位置を並べた配列を返す
+// keysはまだ並べ替えない。values_lenはkeysと一緒に並べ替える配列の要素数
+fn paired_permutation<K, F>(
+    keys: &mut [K],
+    values_len: usize,
+    comparator: &F,
+) -> Result<Vec<usize>, SortError>
+where
+    K: Send,
+    F: Sync + Fn(&K, &K) -> Ordering,
+{
+    if keys.len() != values_len {
+        return Err(SortError::LengthMismatch {
+            keys: keys.len(),
+            values: values_len,
+        });
+    }
+    require_power_of_two(keys.len())?;
+
+    // キーへの可変参照と元の位置の組をソートして、並べ替えの順序を求める
+    // （可変参照はK: Sendだけでスレッド間を移動できるので、K: Syncは要らない）
+    let mut indexed: Vec<(usize, &mut K)> = keys.iter_mut().enumerate().collect();
+    sort_by(&mut indexed, &|(i, a), (j, b)| {
+        comparator(a, b).then_with(|| i.cmp(j))
+    })?;
+    Ok(indexed.into_iter().map(|(i, _)| i).collect())
+}
+
+// apply_permutation_in_placeと同じようにarrayを並べ替えるが、Copyでない要素も扱えるように
+// ptr::readとptr::copy_nonoverlappingで要素を移動する
+// permutationは0..array.len()の並べ替えでなければならない（このモジュールの中でだけ、
+// 正しい並べ替えを渡して使う）
+fn apply_permutation_by_moving<T>(array: &mut [T], permutation: &mut [usize]) {
+    assert_eq!(array.len(), permutation.len());
+    debug_assert!({
+        let mut seen = vec![false; permutation.len()];
+        permutation
+            .iter()
+            .all(|&p| p < seen.len() && !std::mem::replace(&mut seen[p], true))
+    });
+    let base = array.as_mut_ptr();
+    for start in 0..array.len() {
+        if permutation[start] == start {
+            continue;
+        }
+        // 安全性：permutationは正しい並べ替えなので、すべての位置は配列の範囲内にある
+        // firstを読み出してから書き戻すまでの間は、permutationの読み書きしか行わず、
+        // ユーザーのコード（comparatorやdrop）は呼ばれない。permutationの添字も範囲内なので
+        // 途中でパニックすることはなく、同じ要素が2つある状態が外から見えることはない
+        // 巡回置換の中では、各位置に1回だけ書き込み、最後に空いた位置にfirstを書き戻す
+        unsafe {
+            let first = std::ptr::read(base.add(start));
+            let mut current = start;
+            while permutation[current] != start {
+                let next = permutation[current];
+                std::ptr::copy_nonoverlapping(base.add(next), base.add(current), 1);
+                permutation[current] = current;
+                current = next;
+            }
+            std::ptr::write(base.add(current), first);
+            permutation[current] = current;
+        }
+    }
+}
+
+// 実行時に選ぶ比較方法を表すトレイト
+// プラグインのように、比較方法をトレイトオブジェクトとして受け渡すときに使う
+pub trait Comparator<T> {
+    fn compare(&self, a: &T, b: &T) -> Ordering;
+}
+
+// クロージャや関数もそのままComparatorとして使えるようにする
+impl<T, F> Comparator<T> for F
+where
+    F: Fn(&T, &T) -> Ordering,
+{
+    fn compare(&self, a: &T, b: &T) -> Ordering {
+        self(a, b)
+    }
+}
+
+// トレイトオブジェクトのComparatorでソートする（比較は動的ディスパッチになる）
+// トレイトオブジェクトがSyncなので、しきい値以上の配列は並列にソートされる
+pub fn sort_dyn<T: Send>(
+    array: &mut [T],
+    comparator: &(dyn Comparator<T> + Sync),
+) -> Result<(), SortError> {
+    sort_by(array, &|a, b| comparator.compare(a, b))
+}
+
+// Boxに入った要素のスライス（例：Vec<Box<Record>>）を、Boxの中身を比較してソートする
+// 入れ替わるのはBox（ヒープ領域へのポインタ）だけで、ヒープ上の中身は移動しない
+// そのため要素が大きくても交換のコストはポインタ1つ分で済む
+// Tはstrやdyn Traitのようなサイズが決まらない型でもよい
+pub fn sort_boxed_by<T, F>(array: &mut [Box<T>], comparator: &F) -> Result<(), SortError>
+where
+    T: ?Sized + Send,
+    F: Sync + Fn(&T, &T) -> Ordering,
+{
+    sort_by(array, &|a, b| comparator(&**a, &**b))
+}
+
+// ワーカースレッドで配列をソートし、ソート済みの要素を先頭から順にチャネルへ送る
+// バイトニックソートは全体をソートし終えるまで最小の要素が決まらないので、
+// 配列を2つに分けてそれぞれを並列にソートし、最後に2つの列をマージしながら送る
+// マージでは先頭の要素が決まるたびに送るので、受け取る側はマージの完了を待たずに
+// 処理を始められる（最初の要素が届くのは、半分ずつのソートが終わってから）
+// 要素数は2のべき乗でなくてもよい。受け取る側がReceiverを破棄したら送るのをやめる
+pub fn sort_streaming<T>(mut array: Vec<T>, order: SortOrder) -> Receiver<T>
+where
+    T: Ord + Send + 'static,
+{
+    let (sender, receiver) = mpsc::channel();
+    thread::spawn(move || {
+        let second = array.split_off(array.len() / 2);
+        let (first, second) = rayon::join(
+            || sorted_from(array, &order),
+            || sorted_from(second, &order),
+        );
+        // 先頭に置く方の要素を選ぶ。等しければ前半の要素を先に送る
+        let take_first = |a: &T, b: &T| match order {
+            SortOrder::Ascending => a <= b,
+            SortOrder::Descending => a >= b,
+        };
+        let (mut first, mut second) = (first.into_iter().peekable(), second.into_iter().peekable());
+        loop {
+            let value = match (first.peek(), second.peek()) {
+                (Some(a), Some(b)) if take_first(a, b) => first.next(),
+                (Some(_), Some(_)) => second.next(),
+                (Some(_), None) => first.next(),
+                (None, _) => second.next(),
+            };
+            match value {
+                Some(value) => {
+                    if sender.send(value).is_err() {
+                        break;
+                    }
+                }
+                None => break,
+            }
+        }
+    });
+    receiver
+}
+
+// sort_byと同じようにソートするが、rayonのスレッドプールの代わりに
+// std::thread::scopeでスレッドを生成して並列に処理する（rayonを使わない並列処理の経路）
+// rayon::joinも'staticを要求しないので、スタック上のデータへの参照などはsort_byでもソートできる
+// 分割した部分配列の要素数がしきい値以上のときに、前半を新しいスレッドで処理する
+// ただしスレッドを生成するのは再帰の上からlog2(コア数)段までで、それより下の段は
+// 現在のスレッドで順に処理する。同時に動くスレッドの数はコア数程度に収まる
+// スレッドプールを使わない分、スレッドの生成のコストがかかる
+pub fn sort_by_scoped<T, F>(array: &mut [T], comparator: &F) -> Result<(), SortError>
+where
+    T: Send,
+    F: Sync + Fn(&T, &T) -> Ordering,
+{
+    require_power_of_two(array.len())?;
+    if sort_presorted(array, comparator).is_none() {
+        let cores = thread::available_parallelism().map_or(1, |n| n.get());
+        do_sort_scoped(
+            array,
+            true,
+            comparator,
+            parallel_threshold::<T>(),
+            scoped_spawn_depth(cores),
+        );
+    }
+    Ok(())
+}
+
+// cores個のスレッドで処理するために、スレッドを生成する再帰の段数（log2(cores)の切り上げ）
+// 1段ごとにスレッドの数が2倍になるので、depth段で最大2^depth個のスレッドが同時に動く
+fn scoped_spawn_depth(cores: usize) -> u32 {
+    cores.next_power_of_two().trailing_zeros()
+}
+
+// depthはスレッドを生成してよい残りの段数。0になったら、それより下は順に処理する
+fn do_sort_scoped<T, F>(array: &mut [T], is_asc: bool, comparator: &F, threshold: usize, depth: u32)
+where
+    T: Send,
+    F: Sync + Fn(&T, &T) -> Ordering,
+{
+    if array.len() > 1 {
+        let mid_point = array.len() / 2;
+        let (first, second) = array.split_at_mut(mid_point);
+        let parallel = depth > 0 && mid_point > threshold;
+        let child_depth = if parallel { depth - 1 } else { depth };
+        join_scoped(
+            parallel,
+            || do_sort_scoped(first, true, comparator, threshold, child_depth),
+            || do_sort_scoped(second, false, comparator, threshold, child_depth),
+        );
+        sub_sort_scoped(array, is_asc, comparator, threshold, depth);
+    }
+}
+
+fn sub_sort_scoped<T, F>(
+    array: &mut [T],
+    is_asc: bool,
+    comparator: &F,
+    threshold: usize,
+    depth: u32,
+) where
+    T: Send,
+    F: Sync + Fn(&T, &T) -> Ordering,
+{
+    if array.len() > 1 {
+        compare_and_swap(array, is_asc, comparator);
+        let mid_point = array.len() / 2;
+        let (first, second) = array.split_at_mut(mid_point);
+        let parallel = depth > 0 && mid_point >= threshold;
+        let child_depth = if parallel { depth - 1 } else { depth };
+        join_scoped(
+            parallel,
+            || sub_sort_scoped(first, is_asc, comparator, threshold, child_depth),
+            || sub_sort_scoped(second, is_asc, comparator, threshold, child_depth),
+        );
+    }
+}
+
+// parallelがtrueなら、aを新しいスコープ付きスレッドで、bを現在のスレッドで実行する
+// falseなら両方を順に実行する
+fn join_scoped<A, B>(parallel: bool, a: A, b: B)
+where
+    A: FnOnce() + Send,
+    B: FnOnce(),
+{
+    if parallel {
+        thread::scope(|scope| {
+            scope.spawn(a);
+            b();
+        });
+    } else {
+        a();
+        b();
+    }
+}
+
+// バリアントごとに別のフィールドを持つenumのように、そのままでは比較しにくい値を
+// 共通のキー（例：時刻）に射影してソートする。中身はsort_keysと同じで、
+// projectionは要素1つにつき1回だけ呼ばれ、キーが等しい要素は元の順序が保たれる
+// 例：Click { t }とKey { t }の2つのバリアントを持つイベントを、バリアントをまたいで時刻順に並べる
+//   sort_by_projection(&mut events, &|e| match *e {
+//       Event::Click { t } | Event::Key { t } => t,
+//   })
+pub fn sort_by_projection<T, K, F>(array: &mut [T], projection: &F) -> Result<(), SortError>
+where
+    T: Send + Sync,
+    K: Ord + Send,
+    F: Sync + Fn(&T) -> K,
+{
+    sort_keys(array, projection)
+}
+
+// BinaryHeapを消費して、その要素をソート済みのベクタとして返す
+// ヒープの内部のベクタをそのまま取り出してソートするので、要素を1つずつ取り出す
+// BinaryHeap::into_sorted_vecとは異なり、ソートは並列に行われる
+// 要素数は2のべき乗でなくてもよい（sorted_fromと同じく、埋めてから取り除く）
+pub fn sort_from_heap<T: Ord + Send>(heap: BinaryHeap<T>, order: &SortOrder) -> Vec<T> {
+    sorted_from(heap.into_vec(), order)
+}
+
+// sort_by_countingと同じようにソートし、ネットワークが比較を行うたびに、
+// 比較した2要素の位置(i, j)（i < j）をobserverに通知する
+// ネットワークの構造を調べたり、テストで比較の順序を固定したりするときに使う
+// 位置は比較に渡された参照のアドレスから求めるので、do_sortとsub_sortには手を入れていない
+// （要素がゼロサイズ型のときは位置を区別できないので、常に(0, 0)を通知する）
+// ソート済みかどうかの事前の確認は行わず、常にネットワーク全体を実行する
+// しきい値以上の配列では比較が複数のスレッドで行われるので、通知の順序は一定にならない
+pub fn sort_by_observed<T, F, O>(
+    array: &mut [T],
+    comparator: &F,
+    observer: &O,
+) -> Result<SortMetrics, SortError>
+where
+    T: Send,
+    F: Sync + Fn(&T, &T) -> Ordering,
+    O: Sync + Fn(usize, usize),
+{
+    require_power_of_two(array.len())?;
+    let base = array.as_ptr() as usize;
+    let size = std::mem::size_of::<T>().max(1);
+    let position = |value: &T| (value as *const T as usize).wrapping_sub(base) / size;
+    Ok(do_sort(
+        array,
+        true,
+        &|a: &T, b: &T| {
+            observer(position(a), position(b));
+            comparator(a, b)
+        },
+        Tuning::with_threshold(parallel_threshold::<T>()),
+    ))
+}
+
+// arrayをcomparatorの順に並べる置換permと、その逆置換inverseを組で返す（arrayは変更しない）
+// ソート後のi番目の要素は元のperm[i]番目の要素で、元のi番目の要素はソート後のinverse[i]番目にある
+// ソートした配列をinverseで並べ替えれば（new[i] = sorted[inverse[i]]）元の順序に戻せる
+// 要素数は2のべき乗でなくてもよい。比較が等しい要素は元の順序が保たれる（安定）
+pub fn argsort_with_inverse_by<T, F>(array: &[T], comparator: &F) -> (Vec<usize>, Vec<usize>)
+where
+    T: Sync,
+    F: Sync + Fn(&T, &T) -> Ordering,
+{
+    let perm = sort_padded_by((0..array.len()).collect(), &|&i: &usize, &j: &usize| {
+        comparator(&array[i], &array[j]).then_with(|| i.cmp(&j))
+    });
+    let mut inverse = vec![0; perm.len()];
+    for (i, &p) in perm.iter().enumerate() {
+        inverse[p] = i;
+    }
+    (perm, inverse)
+}
+
+// sort_by_controlledに渡す、ソートの取り消しと期限の指定
+// cancelは別のスレッドからtrueにすると、ソートを途中で打ち切る
+// deadlineを過ぎてもソートが終わっていなければ、ソートを途中で打ち切る
+#[derive(Debug, Default)]
+pub struct SortControl {
+    pub cancel: AtomicBool,
+    pub deadline: Option<Instant>,
+}
+
+impl SortControl {
+    // 取り消されておらず、期限もない状態で作る
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // 期限を指定して作る
+    pub fn with_deadline(deadline: Instant) -> Self {
+        Self {
+            cancel: AtomicBool::new(false),
+            deadline: Some(deadline),
+        }
+    }
+
+    // ソートを取り消す（ほかのスレッドから呼んでもよい）
+    pub fn cancel(&self) {
+        self.cancel.store(true, AtomicOrdering::Relaxed);
+    }
+
+    // ソートを続けてよければOk(())を、打ち切るべきなら理由をエラーとして返す
+    // 取り消しと期限切れが両方起きていれば、取り消しを優先する
+    fn check(&self) -> Result<(), SortError> {
+        if self.cancel.load(AtomicOrdering::Relaxed) {
+            Err(SortError::Cancelled)
+        } else if self
+            .deadline
+            .is_some_and(|deadline| Instant::now() >= deadline)
+        {
+            Err(SortError::TimedOut)
+        } else {
+            Ok(())
+        }
+    }
+}
+
+// do_sortとsub_sortの再帰で、ctrlを確認する部分配列の最小の要素数
+// これより小さな部分配列は確認せずに最後まで処理するので、確認のコストは無視できる
+const CONTROL_CHECK_LEN: usize = 1024;
+
+// sort_byと同じようにソートするが、再帰の区切りごとにctrlを確認し、
+// 取り消されていればSortError::Cancelledを、期限を過ぎていればSortError::TimedOutを返す
+// エラーで打ち切ったときは、配列は要素を並べ替えた途中の状態になる（要素が失われることはない）
+pub fn sort_by_controlled<T, F>(
+    array: &mut [T],
+    comparator: &F,
+    ctrl: &SortControl,
+) -> Result<(), SortError>
+where
+    T: Send,
+    F: Sync + Fn(&T, &T) -> Ordering,
+{
+    require_power_of_two(array.len())?;
+    ctrl.check()?;
+    if sort_presorted(array, comparator).is_some() {
+        return Ok(());
+    }
+    let tuning = Tuning::with_threshold(parallel_threshold::<T>());
+    do_sort_controlled(array, true, comparator, tuning, ctrl)
+}
+
+fn do_sort_controlled<T, F>(
+    array: &mut [T],
+    is_asc: bool,
+    comparator: &F,
+    tuning: Tuning,
+    ctrl: &SortControl,
+) -> Result<(), SortError>
+where
+    T: Send,
+    F: Sync + Fn(&T, &T) -> Ordering,
+{
+    if array.len() < CONTROL_CHECK_LEN {
+        do_sort(array, is_asc, comparator, tuning);
+        return Ok(());
+    }
+    ctrl.check()?;
+    let mid_point = array.len() / 2;
+    let (first, second) = array.split_at_mut(mid_point);
+    let (first_result, second_result) = if mid_point > tuning.threshold {
+        rayon::join(
+            || do_sort_controlled(first, true, comparator, tuning, ctrl),
+            || do_sort_controlled(second, false, comparator, tuning, ctrl),
+        )
+    } else {
+        (
+            do_sort_controlled(first, true, comparator, tuning, ctrl),
+            do_sort_controlled(second, false, comparator, tuning, ctrl),
+        )
+    };
+    first_result.and(second_result)?;
+    sub_sort_controlled(array, is_asc, comparator, tuning, ctrl)
+}
+
+fn sub_sort_controlled<T, F>(
+    array: &mut [T],
+    is_asc: bool,
+    comparator: &F,
+    tuning: Tuning,
+    ctrl: &SortControl,
+) -> Result<(), SortError>
+where
+    T: Send,
+    F: Sync + Fn(&T, &T) -> Ordering,
+{
+    if array.len() < CONTROL_CHECK_LEN {
+        sub_sort(array, is_asc, comparator, tuning);
+        return Ok(());
+    }
+    ctrl.check()?;
+    compare_and_swap(array, is_asc, comparator);
+    let mid_point = array.len() / 2;
+    let (first, second) = array.split_at_mut(mid_point);
+    let (first_result, second_result) = if mid_point >= tuning.threshold {
+        rayon::join(
+            || sub_sort_controlled(first, is_asc, comparator, tuning, ctrl),
+            || sub_sort_controlled(second, is_asc, comparator, tuning, ctrl),
+        )
+    } else {
+        (
+            sub_sort_controlled(first, is_asc, comparator, tuning, ctrl),
+            sub_sort_controlled(second, is_asc, comparator, tuning, ctrl),
+        )
+    };
+    first_result.and(second_result)
+}
+
+// VecDequeの要素をソートする
+// リングバッファの要素はメモリ上で2つに分かれていることがあるので、make_contiguousで
+// 1つのスライスにまとめてからソートする（まとめるときに要素の移動が起きることがある）
+// sortと同じく、要素数は2のべき乗でなければならない
+pub fn sort_deque<T: Ord + Send>(
+    deque: &mut VecDeque<T>,
+    order: &SortOrder,
+) -> Result<(), SortError> {
+    require_power_of_two(deque.len())?;
+    sort(deque.make_contiguous(), order)
+}
+
+// 呼び出し元がすでに計算したキーの配列keys（arrayと添字で対応する）の順にarrayをソートする
+// sort_keysのようにキーを取り出し直さないので、キーの計算が重いときに使う
+// keysは変更しない。arrayとkeysの要素数は等しくなければならない
+// キーが等しい要素は元の順序が保たれる（安定ソート）
+pub fn sort_by_external_keys<T, K>(
+    array: &mut [T],
+    keys: &[K],
+    order: &SortOrder,
+) -> Result<(), SortError>
+where
+    T: Send,
+    K: Ord + Sync,
+{
+    if array.len() != keys.len() {
+        return Err(SortError::LengthMismatch {
+            keys: keys.len(),
+            values: array.len(),
+        });
+    }
+    require_power_of_two(keys.len())?;
+    let mut permutation: Vec<usize> = (0..keys.len()).collect();
+    match *order {
+        SortOrder::Ascending => sort_by(&mut permutation, &|&i: &usize, &j: &usize| {
+            keys[i].cmp(&keys[j]).then_with(|| i.cmp(&j))
+        }),
+        SortOrder::Descending => sort_by(&mut permutation, &|&i: &usize, &j: &usize| {
+            keys[j].cmp(&keys[i]).then_with(|| i.cmp(&j))
+        }),
+    }?;
+    apply_permutation(array, &mut permutation);
+    Ok(())
+}
+
+// Cow<str>のスライスを、中身の文字列（str::cmp）で比較してソートする
+// 入れ替わるのはCowの値そのものなので、文字列の複製は起きず、
+// 借用（Borrowed）と所有（Owned）の区別もそのまま保たれる
+pub fn sort_cow_str(array: &mut [Cow<str>], order: &SortOrder) -> Result<(), SortError> {
+    match *order {
+        SortOrder::Ascending => sort_by(array, &|a: &Cow<str>, b: &Cow<str>| str::cmp(a, b)),
+        SortOrder::Descending => sort_by(array, &|a: &Cow<str>, b: &Cow<str>| str::cmp(b, a)),
+    }
+}
+
+// ranks_byで、比較が等しい要素にどう順位を付けるか
+// 順位は1から始まる。例えば [10, 20, 20, 30] の昇順の順位は次のとおり
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RankMethod {
+    Dense,       // 等しい要素は同じ順位で、次の順位は詰める（1, 2, 2, 3）
+    Competition, // 等しい要素は同じ順位で、次の順位は人数分飛ばす（1, 2, 2, 4）
+    Ordinal,     // 等しい要素にも元の順序で別々の順位を付ける（1, 2, 3, 4）
+}
+
+// arrayの各要素の、comparatorの順での順位を返す（arrayは変更しない）
+// 戻り値のi番目は、元のi番目の要素の順位になる
+// 要素数は2のべき乗でなくてもよい
+pub fn ranks_by<T, F>(array: &[T], comparator: &F, method: RankMethod) -> Vec<usize>
+where
+    T: Sync,
+    F: Sync + Fn(&T, &T) -> Ordering,
+{
+    let (perm, _) = argsort_with_inverse_by(array, comparator);
+    let mut ranks = vec![0; perm.len()];
+    let mut rank = 0;
+    for (i, &p) in perm.iter().enumerate() {
+        // ソート後に直前の要素と等しいかどうかで、順位を進めるか決める
+        let tied = i > 0 && comparator(&array[perm[i - 1]], &array[p]) == Ordering::Equal;
+        rank = match method {
+            RankMethod::Ordinal => i + 1,
+            _ if tied => rank,
+            RankMethod::Dense => rank + 1,
+            RankMethod::Competition => i + 1,
+        };
+        ranks[p] = rank;
+    }
+    ranks
+}
+
+// ソートし、各位置の要素がソートの前と変わったかどうかを返す
+// 戻り値のi番目がtrueなら、ソート後のi番目の要素はソート前のi番目の要素と等しくない
+// 等しい値どうしが入れ替わっただけの位置はfalseになる
+// 比較のためにソート前の配列を複製するので、要素数と同じだけのメモリを余分に使う
+pub fn sort_by_with_moved_mask<T, F>(
+    array: &mut [T],
+    comparator: &F,
+) -> Result<Vec<bool>, SortError>
+where
+    T: Clone + PartialEq + Send,
+    F: Sync + Fn(&T, &T) -> Ordering,
+{
+    let original = array.to_vec();
+    sort_by(array, comparator)?;
+    Ok(original
+        .iter()
+        .zip(array.iter())
+        .map(|(before, after)| before != after)
+        .collect())
+}
+
+// arrayの要素をcomparatorの順に、取り出されるたびに1つずつ返すイテレータを作る
+// 全体をソートする代わりにヒープ（二分ヒープ）を作るので、最初の要素を返すまでがO(n)、
+// その後は1要素ごとにO(log n)で済み、k個で取り出すのをやめればO(n + k log n)になる
+// comparatorで等しい要素が返される順序は決まらない（安定ではない）
+// 要素数は2のべき乗でなくてもよい
+pub fn lazy_sorted_by<T, F>(array: Vec<T>, comparator: F) -> impl Iterator<Item = T>
+where
+    F: Fn(&T, &T) -> Ordering,
+{
+    let mut heap = LazySorted {
+        heap: array,
+        comparator,
+    };
+    // 子を持つ最後の要素から根に向かって沈めると、全体がヒープになる
+    for i in (0..heap.heap.len() / 2).rev() {
+        heap.sift_down(i);
+    }
+    heap
+}
+
+// lazy_sorted_byが返すイテレータ
+// heapは、どの要素もその子より前に来る（comparatorでGreaterにならない）ように並べてある
+struct LazySorted<T, F> {
+    heap: Vec<T>,
+    comparator: F,
+}
+
+impl<T, F> LazySorted<T, F>
+where
+    F: Fn(&T, &T) -> Ordering,
+{
+    // i番目の要素を、子より前に来るところまで沈める
+    fn sift_down(&mut self, mut i: usize) {
+        let len = self.heap.len();
+        loop {
+            let left = 2 * i + 1;
+            if left >= len {
+                break;
+            }
+            let right = left + 1;
+            let child = if right < len
+                && (self.comparator)(&self.heap[right], &self.heap[left]) == Ordering::Less
+            {
+                right
+            } else {
+                left
+            };
+            if (self.comparator)(&self.heap[child], &self.heap[i]) != Ordering::Less {
+                break;
+            }
+            self.heap.swap(i, child);
+            i = child;
+        }
+    }
+}
+
+impl<T, F> Iterator for LazySorted<T, F>
+where
+    F: Fn(&T, &T) -> Ordering,
+{
+    type Item = T;
+
+    fn next(&mut self) -> Option<T> {
+        if self.heap.is_empty() {
+            return None;
+        }
+        // 根（先頭）を末尾と入れ替えて取り出し、新しい根を沈める
+        let value = self.heap.swap_remove(0);
+        self.sift_down(0);
+        Some(value)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.heap.len(), Some(self.heap.len()))
+    }
+}
+
+// 独立した多数の配列を、配列ごとに並列にソートする
+// 各配列の中は1つのスレッドで順にソートするので（seq::sort）、小さな配列がたくさんあるときに、
+// 配列の中で細かなタスクを作るよりもタスクの生成が少なくて済む
+// 戻り値のi番目は、arrays[i]をソートした結果になる。要素数が2のべき乗でない配列は
+// ソートせずにエラーを返し、ほかの配列のソートは続ける
+pub fn sort_many<T: Ord + Send>(
+    arrays: &mut [&mut [T]],
+    order: &SortOrder,
+) -> Vec<Result<(), SortError>> {
+    arrays
+        .par_iter_mut()
+        .map(|array| seq::sort(array, order))
+        .collect()
+}
+
+// keyで取り出したキーの順にソートし、キーが等しい要素はそれぞれ1つだけ残す
+// 残るのはソートした順序で各グループの先頭の要素で、同じキーの要素の中で
+// 元のarrayで最も前にあったものになる（キーが等しい要素の順序は保たれる）
+// 残りの要素は取り除いてdropし、arrayの長さを残った要素の数に切り詰める
+// 要素数は2のべき乗でなくてもよい
+pub fn sort_dedup_by_key<T, K, F>(array: &mut Vec<T>, key: &F)
+where
+    T: Send,
+    K: Ord,
+    F: Sync + Fn(&T) -> K,
+{
+    // 元の位置を組にして比較すれば、キーが等しい要素は元の順序で並ぶ
+    let indexed: Vec<(usize, T)> = std::mem::take(array).into_iter().enumerate().collect();
+    let mut sorted = sort_padded_by(indexed, &|a: &(usize, T), b: &(usize, T)| {
+        key(&a.1).cmp(&key(&b.1)).then(a.0.cmp(&b.0))
+    });
+    sorted.dedup_by(|later, kept| key(&later.1) == key(&kept.1));
+    array.extend(sorted.into_iter().map(|(_, value)| value));
+}
+
+// HashMapの要素を、キーの順に並べた(キー, 値)の組のベクタにする
+// HashMapのキーは重複しないので、キーだけで順序が一意に決まる
+// 要素数は2のべき乗でなくてもよい（sort_padded_byで埋めてからソートし、取り除く）
+pub fn sort_map_by_key<K, V, S>(map: HashMap<K, V, S>, order: &SortOrder) -> Vec<(K, V)>
+where
+    K: Ord + Send,
+    V: Send,
+{
+    let entries: Vec<(K, V)> = map.into_iter().collect();
+    match *order {
+        SortOrder::Ascending => sort_padded_by(entries, &|a: &(K, V), b: &(K, V)| a.0.cmp(&b.0)),
+        SortOrder::Descending => sort_padded_by(entries, &|a: &(K, V), b: &(K, V)| b.0.cmp(&a.0)),
+    }
+}
+
+// スライスとして借用できない、添字で要素にアクセスするコンテナ（ページに分けた配列など）の
+// 先頭len個の要素をソートする
+// 要素の比較と交換をすべてIndexとIndexMutで行うので、スライスを使うsort_byより遅く、
+// 並列にも処理しない
+// 添字で同時に2つの要素を可変で借用できないので、交換はmem::takeで一方の値を取り出して行う
+// そのためTにはDefaultが必要になる
+// lenがコンテナの要素数より大きいときは、コンテナの添字の範囲外でパニックする
+pub fn sort_indexed<C, T, F>(container: &mut C, len: usize, comparator: &F) -> Result<(), SortError>
+where
+    C: IndexMut<usize, Output = T> + ?Sized,
+    T: Default,
+    F: Fn(&T, &T) -> Ordering,
+{
+    require_power_of_two(len)?;
+    do_sort_indexed(container, 0..len, true, comparator);
+    Ok(())
+}
+
+// do_sortと同じ再帰を、部分配列の代わりに添字の範囲rangeで行う
+fn do_sort_indexed<C, T, F>(container: &mut C, range: Range<usize>, forward: bool, comparator: &F)
+where
+    C: IndexMut<usize, Output = T> + ?Sized,
+    T: Default,
+    F: Fn(&T, &T) -> Ordering,
+{
+    if range.len() > 1 {
+        let mid_point = range.start + range.len() / 2;
+        do_sort_indexed(container, range.start..mid_point, true, comparator);
+        do_sort_indexed(container, mid_point..range.end, false, comparator);
+        sub_sort_indexed(container, range, forward, comparator);
+    }
+}
+
+fn sub_sort_indexed<C, T, F>(container: &mut C, range: Range<usize>, forward: bool, comparator: &F)
+where
+    C: IndexMut<usize, Output = T> + ?Sized,
+    T: Default,
+    F: Fn(&T, &T) -> Ordering,
+{
+    if range.len() > 1 {
+        let half = range.len() / 2;
+        let mid_point = range.start + half;
+        let out_of_order = if forward {
+            Ordering::Greater
+        } else {
+            Ordering::Less
+        };
+        for i in range.start..mid_point {
+            if comparator(&container[i], &container[i + half]) == out_of_order {
+                let value = std::mem::take(&mut container[i]);
+                container[i] = std::mem::replace(&mut container[i + half], value);
+            }
+        }
+        sub_sort_indexed(container, range.start..mid_point, forward, comparator);
+        sub_sort_indexed(container, mid_point..range.end, forward, comparator);
+    }
+}
+
+// バイトニックネットワークの変形を作るための、do_sortの再帰で前半と後半の向きを選べる版
+// direction_for_level(level)は再帰の深さlevel（配列全体が0）で、部分配列を2つに分けたときの
+// 向きを返す。trueなら前半を昇順、後半を降順に（do_sortと同じ）、falseなら前半を降順、
+// 後半を昇順にソートする。どちらの並びもバイトニック列になるので、マージの結果は変わらない
+// 配列全体は、最後にcomparatorの順（昇順）にマージする
+// 常にtrueを返す関数を渡せば、sort_byと同じ比較と交換を行う
+pub fn do_sort_with_direction_fn<T, F, D>(
+    array: &mut [T],
+    direction_for_level: &D,
+    comparator: &F,
+) -> Result<(), SortError>
+where
+    T: Send,
+    F: Sync + Fn(&T, &T) -> Ordering,
+    D: Sync + Fn(usize) -> bool,
+{
+    require_power_of_two(array.len())?;
+    let tuning = Tuning::with_threshold(parallel_threshold::<T>());
+    do_sort_directed(array, true, 0, direction_for_level, comparator, tuning);
+    Ok(())
+}
+
+fn do_sort_directed<T, F, D>(
+    array: &mut [T],
+    is_asc: bool,
+    level: usize,
+    direction_for_level: &D,
+    comparator: &F,
+    tuning: Tuning,
+) where
+    T: Send,
+    F: Sync + Fn(&T, &T) -> Ordering,
+    D: Sync + Fn(usize) -> bool,
+{
+    if array.len() > 1 {
+        let mid_point = array.len() / 2;
+        let first_asc = direction_for_level(level);
+        let (first, second) = array.split_at_mut(mid_point);
+        let sort_half = |half: &mut [T], is_asc: bool| {
+            do_sort_directed(
+                half,
+                is_asc,
+                level + 1,
+                direction_for_level,
+                comparator,
+                tuning,
+            )
+        };
+        if mid_point > tuning.threshold {
+            rayon::join(
+                || sort_half(first, first_asc),
+                || sort_half(second, !first_asc),
+            );
+        } else {
+            sort_half(first, first_asc);
+            sort_half(second, !first_asc);
+        }
+        sub_sort(array, is_asc, comparator, tuning);
+    }
+}
+
+// 昇順にソート済みだった配列で、dirtyの位置の要素だけが書き換えられたときに、
+// 全体をソートし直さずに昇順に戻す
+// 書き換えられた要素を末尾に移してから、1つずつ二分探索で挿入位置を求め、
+// その位置までを回転させて挿入する。書き換えられていない要素の順序は変わらない
+// 書き換えられた要素がk個なら、比較はO(k log n)回だが、回転で最大O(k n)個の要素を移動する
+// 全体のソート（sort）はO(n log^2 n)回の比較と交換を行うので、kがlog^2 nより十分小さい
+// （書き換えがごく少ない）ときに使う。kがそれに近づくならsortを使う方がよい
+// dirtyに重複があってもよい。要素数は2のべき乗でなくてもよい
+// dirtyに配列の範囲外の位置があるとパニックする
+pub fn resort_after_changes<T: Ord + Send>(array: &mut [T], dirty: &[usize]) {
+    let mut dirty = dirty.to_vec();
+    dirty.sort_unstable();
+    dirty.dedup();
+    let len = array.len();
+    assert!(
+        dirty.last().is_none_or(|&i| i < len),
+        "dirty index out of range for slice of length {}",
+        len
+    );
+
+    // 後ろの位置から順に末尾へ移すと、まだ移していない位置がずれない
+    for (moved, &i) in dirty.iter().rev().enumerate() {
+        array[i..len - moved].rotate_left(1);
+    }
+    // 先頭のlen - k個はソート済みなので、末尾の要素を1つずつ挿入していく
+    for j in len - dirty.len()..len {
+        let position = array[..j].partition_point(|value| *value <= array[j]);
+        array[position..=j].rotate_right(1);
+    }
+}
+
+// sort_byなどでcomparatorの順にソートしたsortedから、targetを二分探索する
+// 見つかればOk(位置)を、見つからなければErr(順序を崩さずにtargetを挿入できる位置)を返す
+// slice::binary_searchと同じ規則で、sortedと同じcomparatorを使って比較する
+// （降順にソートした配列でも、降順の比較関数を渡せば探索できる）
+// 等しい要素が複数あるときは、そのどれかの位置を返す
+pub fn search_by<T, F>(sorted: &[T], target: &T, comparator: &F) -> Result<usize, usize>
+where
+    F: Fn(&T, &T) -> Ordering,
+{
+    sorted.binary_search_by(|probe| comparator(probe, target))
+}
+
+// orderの順にソート済みのsortedに、まだソートしていないbatchの要素を加える
+// batchだけをソートしてから（sorted_from）、sortedとマージするので、全体を連結して
+// ソートし直すよりずっと少ない比較で済む。マージはsortedとbatchの要素数の和に比例する時間で終わる
+// sortedの要素とbatchの要素が等しいときは、sortedの要素を前に置く
+// batchの要素数は2のべき乗でなくてもよい
+pub fn insert_sorted_batch<T: Ord + Send>(sorted: &mut Vec<T>, batch: Vec<T>, order: &SortOrder) {
+    let batch = sorted_from(batch, order);
+    let existing = std::mem::take(sorted);
+    *sorted = match *order {
+        SortOrder::Ascending => merge_two(existing, batch, &|a: &T, b: &T| a.cmp(b)),
+        SortOrder::Descending => merge_two(existing, batch, &|a: &T, b: &T| b.cmp(a)),
+    };
+}
+
+// 基準の値からの距離が近い順にソートする
+// metricは要素から基準までの距離を求める関数で、距離は最初に要素ごとに一度だけ求める（sort_keys）
+// 例：原点からの距離の2乗 |p: &Point| p.x * p.x + p.y * p.y
+// 距離が等しい要素は元の順序が保たれる（安定ソート）
+pub fn sort_by_distance<T, D, F>(array: &mut [T], metric: &F) -> Result<(), SortError>
+where
+    T: Send + Sync,
+    D: Ord + Send,
+    F: Sync + Fn(&T) -> D,
+{
+    sort_keys(array, metric)
+}
+
+// 要素数が2のべき乗になるまでT::default()で埋めてからソートし、埋めた分を取り除く
+// 埋める値を呼び出し側で用意しなくてよく、Option<T>で包む（sorted_from）よりメモリも少なくて済む
+// ソート後はdefaultと等しい要素が1か所に集まるので、その先頭から埋めた数だけ取り除く
+// defaultがデータの最小値より小さければ先頭から、データの途中の値と等しければその位置から
+// 取り除くことになる。元のデータにdefaultと等しい要素があっても、その数は変わらない
+// （Ordで等しい要素は区別しないので、取り除くのが埋めた要素とは限らない）
+pub fn sort_vec_default_padded<T>(v: &mut Vec<T>, order: &SortOrder)
+where
+    T: Default + Ord + Send,
+{
+    let len = v.len();
+    let padded_len = len.next_power_of_two();
+    v.resize_with(padded_len, T::default);
+    sort(v, order).expect("the padded length must be a power of two");
+
+    let padding = T::default();
+    let start = match *order {
+        SortOrder::Ascending => v.partition_point(|value| *value < padding),
+        SortOrder::Descending => v.partition_point(|value| *value > padding),
+    };
+    v.drain(start..start + (padded_len - len));
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::{Rng, SeedableRng};
+    use rand_pcg::Pcg64Mcg;
+    use std::borrow::Cow;
+    use std::cmp::{Ordering, Reverse};
+    use std::collections::{BinaryHeap, HashMap, HashSet, VecDeque};
+    use std::mem::MaybeUninit;
+    use std::sync::Arc;
+    use std::time::{Duration, Instant};
+
+    #[cfg(feature = "tokio")]
+    use super::sort_by_async;
+    use super::{
+        adaptive_threshold, apply_permutation, apply_permutation_by_moving,
+        apply_permutation_in_place, argsort_with_inverse_by, compare_and_swap_at, compare_backends,
+        do_sort, do_sort_scoped, do_sort_with_direction_fn, insert_sorted_batch, lazy_sorted_by,
+        parallel_threshold, parse_threshold, partition_by, ranks_by, resort_after_changes,
+        reverse_in_place, scoped_spawn_depth, search_by, sort, sort_adaptive, sort_adaptive_runs,
+        sort_and_group_by, sort_and_runs_by, sort_arc_by, sort_array, sort_boxed_by, sort_by,
+        sort_by_benchmarked, sort_by_controlled, sort_by_counting, sort_by_dir, sort_by_distance,
+        sort_by_external_keys, sort_by_iterative, sort_by_local, sort_by_observed,
+        sort_by_projection, sort_by_reporting, sort_by_scoped, sort_by_skipping_sorted,
+        sort_by_stage_timings, sort_by_timed, sort_by_two, sort_by_with_cost_hint,
+        sort_by_with_moved_mask, sort_by_with_stage_stats, sort_by_with_threshold, sort_cow,
+        sort_cow_str, sort_dedup_by_key, sort_deque, sort_dir, sort_dyn, sort_from_heap,
+        sort_indexed, sort_keys, sort_keys_copied, sort_many, sort_map_by_key, sort_maybe_uninit,
+        sort_or_panic, sort_outcome, sort_paired_by, sort_refs_by, sort_soa_by, sort_streaming,
+        sort_vec_default_padded, sorted_from, three_way_partition_by, Comparator, RankMethod,
+        SortControl, SortOutcome, Tuning,
+    };
+    use crate::utils::{
+        is_permutation_of, is_sorted_ascending, is_sorted_descending, new_duration_vec,
+        new_payload_vec, new_test_case, new_u32_vec, Payload,
+    };
+    use crate::SortOrder::*;
+    use crate::{SortError, SortMetrics};
+
+    // 構造体Studentを定義する
+    // 構造体は関連する値を1つにまとめたデータ構造。複数のデータフィールドを持つ
+
+    // deriveアトリビュートを使い、DebugトレイトとPartialEqトレイトの実装を自動導出する
+    #[derive(Debug, PartialEq)]
+    struct Student {
+        first_name: String, // first_name（名前）フィールド。String型
+        last_name: String,  // last_name（苗字）フィールド。String型
+        age: u8,            // age（年齢）フィールド。u8型（8ビット符号なし整数）
+    }
+
+    // implブロックを使うと対象の型に関連関数やメソッドを実装できる
+    impl Student {
+        // 関連関数newを定義する
+        fn new(first_name: &str, last_name: &str, age: u8) -> Self {
+            // 構造体Studentを初期化して返す。Selfはimpl対象の型（Student）の別名
+            Self {
+                // to_stringメソッドで&str型の引数からString型の値を作る。詳しくは5章で説明
+                first_name: first_name.to_string(), // first_nameフィールドに値を設定
+                last_name: last_name.to_string(),   // last_nameフィールドに値を設定
+                age,                                // ageフィールドにage変数の値を設定
+                                                    // フィールドと変数が同じ名前のときは、このように省略形で書ける
+            }
+        }
+    }
+
+    #[test]
+    fn sort_to_fail() {
+        let mut x = vec![10, 30, 11]; // x.len() が2のべき乗になっていない。
+        assert!(sort(&mut x, &Ascending).is_err());
+    }
+
+    #[test]
+    fn sort_u32_ascending() {
+        let mut x: Vec<u32> = vec![10, 30, 11, 20, 4, 330, 21, 110];
+        assert_eq!(sort(&mut x, &Ascending), Ok(()));
+        assert_eq!(x, vec![4, 10, 11, 20, 21, 30, 110, 330]);
+    }
+
+    #[test]
+    fn sort_u32_descending() {
+        let mut x: Vec<u32> = vec![10, 30, 11, 20, 4, 330, 21, 110];
+        assert_eq!(sort(&mut x, &Descending), Ok(()));
+        assert_eq!(x, vec![330, 110, 30, 21, 20, 11, 10, 4]);
+    }
+
+    #[test]
+    fn sort_u32_large() {
+        {
+            // 乱数で65,536要素のデータ列を作る（65,536は2の16乗）
+            let mut x = new_u32_vec(65536 * 2 * 2 * 2 * 2 * 2 * 2 * 2);
+            let now = Instant::now();
+            // 昇順にソートする
+            assert_eq!(sort(&mut x, &Ascending), Ok(()));
+            println!("sorted: {:?}", now.elapsed());
+            // ソート結果が正しいことを検証する
+            assert!(is_sorted_ascending(&x));
+        }
+        // {
+        //     let mut x = new_u32_vec(65536);
+        //     assert_eq!(sort(&mut x, &Descending), Ok(()));
+        //     assert!(is_sorted_descending(&x));
+        // }
+    }
+
+    #[test]
+    fn sort_str_ascending() {
+        let mut x = vec![
+            "Rust",
+            "is",
+            "fast",
+            "and",
+            "memory-efficient",
+            "with",
+            "no",
+            "GC",
+        ];
+        assert_eq!(sort(&mut x, &Ascending), Ok(()));
+        assert_eq!(
+            x,
+            vec![
+                "GC",
+                "Rust",
+                "and",
+                "fast",
+                "is",
+                "memory-efficient",
+                "no",
+                "with"
+            ]
+        );
+    }
+
+    #[test]
+    fn sort_str_descending() {
+        let mut x = vec![
+            "Rust",
+            "is",
+            "fast",
+            "and",
             "memory-efficient",
             "with",
             "no",
             "GC",
         ];
+        assert_eq!(sort(&mut x, &Descending), Ok(()));
+        assert_eq!(
+            x,
+            vec![
+                "with",
+                "no",
+                "memory-efficient",
+                "is",
+                "fast",
+                "and",
+                "Rust",
+                "GC"
+            ]
+        );
+    }
+
+    #[test]
+    // 年齢で昇順にソートする
+    fn sort_students_by_age_ascending() {
+        // 4人分のテストデータを作成
+        let taro = Student::new("Taro", "Yamada", 16);
+        let hanako = Student::new("Hanako", "Yamada", 14);
+        let kyoko = Student::new("Kyoko", "Ito", 15);
+        let ryosuke = Student::new("Ryosuke", "Hayashi", 17);
+
+        // ソート対象のベクタを作成する
+        let mut x = vec![&taro, &hanako, &kyoko, &ryosuke];
+
+        // ソート後の期待値を作成する
+        let expected = vec![&hanako, &kyoko, &taro, &ryosuke];
+
+        assert_eq!(
+            // sort_by関数でソートする。第2引数はソート順を決めるクロージャ
+            // 引数に2つのStudent構造体をとり、ageフィールドの値をcmpメソッドで
+            // 比較することで大小を決定する
+            sort_by(&mut x, &|a, b| a.age.cmp(&b.age)),
+            Ok(())
+        );
+
+        // 結果を検証する
+        assert_eq!(x, expected);
+    }
+
+    #[test]
+    fn sort_students_by_name_ascending() {
+        let taro = Student::new("Taro", "Yamada", 16);
+        let hanako = Student::new("Hanako", "Yamada", 14);
+        let kyoko = Student::new("Kyoko", "Ito", 15);
+        let ryosuke = Student::new("Ryosuke", "Hayashi", 17);
+
+        let mut x = vec![&taro, &hanako, &kyoko, &ryosuke];
+        let expected = vec![&ryosuke, &kyoko, &hanako, &taro];
+
+        assert_eq!(
+            sort_by(
+                &mut x,
+                // まずlast_nameを比較する
+                &|a, b| a
+                    .last_name
+                    .cmp(&b.last_name)
+                    // もしlast_nameが等しくない（LessまたはGreater）ならそれを返す
+                    // last_nameが等しい（Equal）ならfirst_nameを比較する
+                    .then_with(|| a.first_name.cmp(&b.first_name))
+            ),
+            Ok(())
+        );
+        assert_eq!(x, expected);
+    }
+
+    #[test]
+    fn partition_all_true() {
+        let mut x = vec![1, 3, 5, 7, 9];
+        assert_eq!(partition_by(&mut x, &|v| v % 2 == 1), 0);
+        assert_eq!(x, vec![1, 3, 5, 7, 9]);
+    }
+
+    #[test]
+    fn partition_all_false() {
+        let mut x = vec![2, 4, 6, 8, 10];
+        assert_eq!(partition_by(&mut x, &|v| v % 2 == 1), 5);
+        assert_eq!(x, vec![2, 4, 6, 8, 10]);
+    }
+
+    #[test]
+    fn partition_mixed() {
+        let mut x = vec![5, 2, 7, 8, 1, 4, 3];
+        // 偶数（false）が前、奇数（true）が後ろ。グループ内の順序は保たれる
+        assert_eq!(partition_by(&mut x, &|v| v % 2 == 1), 3);
+        assert_eq!(x, vec![2, 8, 4, 5, 7, 1, 3]);
+    }
+
+    #[test]
+    fn partition_large() {
+        let original = new_u32_vec(100_000);
+        let mut x = original.clone();
+        let falses = partition_by(&mut x, &|v| v % 3 == 0);
+
+        // 並列に処理しても、逐次処理（filter）と同じ結果になることを検証する
+        let mut expected: Vec<u32> = original.iter().cloned().filter(|v| v % 3 != 0).collect();
+        assert_eq!(falses, expected.len());
+        expected.extend(original.iter().cloned().filter(|v| v % 3 == 0));
+        assert_eq!(x, expected);
+    }
+
+    #[test]
+    fn sort_maybe_uninit_buffer() {
+        let mut buffer: Vec<MaybeUninit<u32>> = (0..10).map(|_| MaybeUninit::uninit()).collect();
+        // 先頭の8要素だけに値を書き込む
+        for (slot, value) in buffer.iter_mut().zip(&[10, 30, 11, 20, 4, 330, 21, 110]) {
+            *slot = MaybeUninit::new(*value);
+        }
+        assert_eq!(
+            unsafe { sort_maybe_uninit(&mut buffer, 8, &Ascending) },
+            Ok(())
+        );
+
+        let sorted: Vec<u32> = buffer[..8]
+            .iter()
+            .map(|v| unsafe { v.assume_init() })
+            .collect();
+        assert_eq!(sorted, vec![4, 10, 11, 20, 21, 30, 110, 330]);
+    }
+
+    #[test]
+    fn sort_maybe_uninit_to_fail() {
+        let mut buffer = [
+            MaybeUninit::new(3u32),
+            MaybeUninit::new(1),
+            MaybeUninit::new(2),
+        ];
+        assert!(unsafe { sort_maybe_uninit(&mut buffer, 3, &Descending) }.is_err());
+    }
+
+    // メモリ予算（MB）に収まる最大の2のべき乗の要素数を求める
+    // 予算が0MBなら1要素も収まらないので、パニックする
+    fn largest_power_of_two_len<T>(budget_mb: usize) -> usize {
+        assert!(budget_mb > 0, "the memory budget must be at least 1 MB");
+        let max_len = budget_mb * 1024 * 1024 / std::mem::size_of::<T>();
+        // max_len以下で最大の2のべき乗
+        1 << (usize::BITS - 1 - max_len.leading_zeros())
+    }
+
+    // 非常に大きな配列をソートするテスト。時間とメモリを大量に使うので通常は実行しない
+    // `cargo test --release -- --ignored` で実行できる
+    // メモリ予算は環境変数BITONIC_MEMORY_BUDGET_MBで指定する（デフォルトは1024MB）
+    // デフォルトの予算で確認しているのは2^28要素（1GB）まで
+    // 添字やmid_pointはすべてusizeで計算しているので、64ビット環境では2^31を超える
+    // 要素数も扱えるはずだが、確かめるには16GB以上のメモリでBITONIC_MEMORY_BUDGET_MB=16384
+    // （2^32要素）を指定して実行する
+    #[test]
+    #[ignore]
+    fn sort_u32_within_memory_budget() {
+        let budget_mb = std::env::var("BITONIC_MEMORY_BUDGET_MB")
+            .ok()
+            .and_then(|v| v.parse().ok())
+            .unwrap_or(1024);
+        let len = largest_power_of_two_len::<u32>(budget_mb);
+        let mut x = new_u32_vec(len);
+        assert_eq!(sort(&mut x, &Ascending), Ok(()));
+        assert!(is_sorted_ascending(&x));
+    }
+
+    #[test]
+    fn largest_power_of_two_len_within_budget() {
+        assert_eq!(largest_power_of_two_len::<u32>(1), 262_144);
+        assert_eq!(largest_power_of_two_len::<u32>(3), 524_288);
+        assert_eq!(largest_power_of_two_len::<u64>(1024), 134_217_728);
+        // 2^31を超える要素数を試すための予算
+        #[cfg(target_pointer_width = "64")]
+        assert_eq!(largest_power_of_two_len::<u32>(16384), 1 << 32);
+    }
+
+    #[test]
+    #[should_panic(expected = "the memory budget must be at least 1 MB")]
+    fn largest_power_of_two_len_rejects_zero_budget() {
+        largest_power_of_two_len::<u32>(0);
+    }
+
+    #[test]
+    fn sort_cow_borrowed_when_sorted() {
+        let x = vec![4, 10, 11, 20, 21, 30, 110, 330];
+        let sorted = sort_cow(&x, &Ascending).unwrap();
+        assert!(matches!(sorted, Cow::Borrowed(_)));
+        assert_eq!(&*sorted, &x[..]);
+    }
+
+    #[test]
+    fn sort_cow_owned_when_unsorted() {
+        let x = vec![10, 30, 11, 20, 4, 330, 21, 110];
+        let sorted = sort_cow(&x, &Descending).unwrap();
+        assert!(matches!(sorted, Cow::Owned(_)));
+        assert_eq!(&*sorted, &[330, 110, 30, 21, 20, 11, 10, 4]);
+        // 元のデータは変更されない
+        assert_eq!(x, vec![10, 30, 11, 20, 4, 330, 21, 110]);
+    }
+
+    #[test]
+    fn parallel_threshold_by_size() {
+        assert_eq!(parallel_threshold::<u8>(), 4096);
+        assert_eq!(parallel_threshold::<u64>(), 4096);
+        assert_eq!(parallel_threshold::<Payload<64>>(), 512);
+        assert_eq!(parallel_threshold::<Payload<256>>(), 256);
+        // 非常に大きな要素でも下限を下回らない
+        assert_eq!(parallel_threshold::<Payload<4096>>(), 256);
+        // ゼロサイズ型でも0にならない
+        assert_eq!(parallel_threshold::<()>(), 4096);
+    }
+
+    #[test]
+    fn sort_payload_structs() {
+        let mut x = new_payload_vec::<64>(2048);
+        assert_eq!(sort(&mut x, &Ascending), Ok(()));
+        assert!(is_sorted_ascending(&x));
+    }
+
+    #[test]
+    fn sort_by_reporting_sorted_input() {
+        let mut x: Vec<u32> = vec![4, 10, 11, 20, 21, 30, 110, 330];
+        assert_eq!(sort_by_reporting(&mut x, &|a, b| a.cmp(b)), Ok(false));
+        assert_eq!(x, vec![4, 10, 11, 20, 21, 30, 110, 330]);
+    }
+
+    #[test]
+    fn sort_by_reporting_unsorted_input() {
+        let mut x: Vec<u32> = vec![10, 30, 11, 20, 4, 330, 21, 110];
+        assert_eq!(sort_by_reporting(&mut x, &|a, b| a.cmp(b)), Ok(true));
+        assert_eq!(x, vec![4, 10, 11, 20, 21, 30, 110, 330]);
+    }
+
+    #[test]
+    fn sort_by_counting_metrics() {
+        let mut x: Vec<u32> = vec![10, 30, 11, 20, 4, 330, 21, 110];
+        let metrics = sort_by_counting(&mut x, &|a, b| a.cmp(b)).unwrap();
+        // 要素数8のバイトニックソートは24回比較する
+        assert_eq!(metrics.comparisons, 24);
+        assert!(metrics.swaps > 0);
+        assert_eq!(metrics.parallel_tasks, 0);
+        assert!(sort_by_counting(&mut x[..3], &|a, b| a.cmp(b)).is_err());
+    }
+
+    #[test]
+    fn sort_adaptive_small_array() {
+        let mut x: Vec<u32> = vec![10, 30, 11, 20, 4, 330, 21, 110];
+        assert_eq!(sort_adaptive(&mut x, &Ascending), Ok(()));
+        assert_eq!(x, vec![4, 10, 11, 20, 21, 30, 110, 330]);
+
+        // コア数が多くても、小さな配列ではタスクを1つも生成しない
+        let threshold = adaptive_threshold::<u32>(x.len(), 64);
+        assert_eq!(threshold, usize::MAX);
+        let mut x = new_u32_vec(1024);
+        let metrics = do_sort(
+            &mut x,
+            true,
+            &|a: &u32, b: &u32| a.cmp(b),
+            Tuning::with_threshold(threshold),
+        );
+        assert_eq!(metrics.parallel_tasks, 0);
+        assert!(is_sorted_ascending(&x));
+    }
+
+    #[test]
+    fn sort_adaptive_large_array() {
+        assert_eq!(adaptive_threshold::<u32>(65536, 1), usize::MAX);
+        assert_eq!(adaptive_threshold::<u32>(65536, 4), 4096);
+        assert_eq!(adaptive_threshold::<u32>(1 << 20, 4), 65536);
+
+        let mut x = new_u32_vec(65536);
+        assert_eq!(sort_adaptive(&mut x, &Descending), Ok(()));
+        assert!(is_sorted_descending(&x));
+    }
+
+    #[test]
+    fn sort_student_refs_by_age_ascending() {
+        let taro = Student::new("Taro", "Yamada", 16);
+        let hanako = Student::new("Hanako", "Yamada", 14);
+        let kyoko = Student::new("Kyoko", "Ito", 15);
+        let ryosuke = Student::new("Ryosuke", "Hayashi", 17);
+
+        let mut x = vec![&taro, &hanako, &kyoko, &ryosuke];
+        let expected = vec![&hanako, &kyoko, &taro, &ryosuke];
+
+        // 比較関数は参照の参照（&&Student）ではなく&Studentを受け取る
+        assert_eq!(
+            sort_refs_by(&mut x, &|a: &Student, b: &Student| a.age.cmp(&b.age)),
+            Ok(())
+        );
+        assert_eq!(x, expected);
+    }
+
+    #[test]
+    fn sort_keys_reverse_descending() {
+        let mut x: Vec<u32> = vec![10, 30, 11, 20, 4, 330, 21, 110];
+        // Reverseでキーを包むと降順にソートされる
+        assert_eq!(sort_keys(&mut x, &|v| Reverse(*v)), Ok(()));
+        assert_eq!(x, vec![330, 110, 30, 21, 20, 11, 10, 4]);
+    }
+
+    #[test]
+    fn sort_keys_string_key_is_stable() {
+        let taro = Student::new("Taro", "Yamada", 16);
+        let hanako = Student::new("Hanako", "Yamada", 14);
+        let kyoko = Student::new("Kyoko", "Ito", 15);
+        let ryosuke = Student::new("Ryosuke", "Hayashi", 17);
+
+        let mut x = vec![&taro, &hanako, &kyoko, &ryosuke];
+        // Copyでないキー（String）でソートする。苗字が同じ2人は元の順序のまま
+        assert_eq!(sort_keys(&mut x, &|s| s.last_name.clone()), Ok(()));
+        assert_eq!(x, vec![&ryosuke, &kyoko, &taro, &hanako]);
+    }
+
+    #[test]
+    fn sort_keys_large() {
+        let mut x = new_u32_vec(65536);
+        assert_eq!(sort_keys(&mut x, &|v| *v), Ok(()));
+        assert!(is_sorted_ascending(&x));
+        assert!(sort_keys(&mut x[..3], &|v| *v).is_err());
+    }
+
+    #[test]
+    fn apply_permutation_cycles() {
+        let mut x = vec!['a', 'b', 'c', 'd', 'e'];
+        apply_permutation(&mut x, &mut [4, 0, 1, 3, 2]);
+        assert_eq!(x, vec!['e', 'a', 'b', 'd', 'c']);
+    }
+
+    #[test]
+    fn sort_all_equal_elements() {
+        // すべての要素が等しい配列は、バイトニック列としては退化したケース
+        // compare_and_swapは比較結果がGreater（降順ではLess）のときだけ交換するので、
+        // 等しい要素どうしは一度も交換されない
+        let mut x = vec![7u32; 1024];
+        assert_eq!(sort(&mut x, &Ascending), Ok(()));
+        assert_eq!(x, vec![7u32; 1024]);
+        assert_eq!(sort(&mut x, &Descending), Ok(()));
+        assert_eq!(x, vec![7u32; 1024]);
+
+        // sortはソート済みの入力をネットワークにかけないので、do_sortを直接呼び出して、
+        // ネットワーク全体（256 * 10 * 11回の比較）を実行しても交換しないことを確かめる
+        let tuning = Tuning::with_threshold(parallel_threshold::<u32>());
+        for &is_asc in &[true, false] {
+            let metrics = do_sort(&mut x, is_asc, &|a: &u32, b: &u32| a.cmp(b), tuning);
+            assert_eq!(metrics.comparisons, 256 * 10 * 11);
+            assert_eq!(metrics.swaps, 0);
+            assert_eq!(x, vec![7u32; 1024]);
+        }
+    }
+
+    #[test]
+    fn sort_all_equal_keys_keeps_order() {
+        // キーが等しい要素は交換されないので、キー以外が異なっても順序は変わらない
+        // sort_presortedで打ち切られないように、do_sortを直接呼び出す
+        let tuning = Tuning::with_threshold(parallel_threshold::<(u32, usize)>());
+        for &is_asc in &[true, false] {
+            let mut x: Vec<(u32, usize)> = (0..1024).map(|i| (7, i)).collect();
+            let metrics = do_sort(
+                &mut x,
+                is_asc,
+                &|a: &(u32, usize), b: &(u32, usize)| a.0.cmp(&b.0),
+                tuning,
+            );
+            assert_eq!(metrics.comparisons, 256 * 10 * 11);
+            assert_eq!(metrics.swaps, 0);
+            assert!(x.iter().enumerate().all(|(i, v)| v.1 == i));
+        }
+    }
+
+    #[test]
+    fn sorted_from_range() {
+        // 要素数100は2のべき乗ではない
+        let x = sorted_from((0..100).rev(), &Ascending);
+        assert_eq!(x, (0..100).collect::<Vec<_>>());
+        let x = sorted_from(0..100, &Descending);
+        assert_eq!(x, (0..100).rev().collect::<Vec<_>>());
+        assert_eq!(sorted_from(0..0, &Ascending), Vec::<i32>::new());
+    }
+
+    #[test]
+    fn sorted_from_hash_set() {
+        let set: HashSet<&str> = ["Rust", "is", "fast", "and", "memory-efficient"]
+            .iter()
+            .cloned()
+            .collect();
+        let x = sorted_from(set, &Ascending);
+        assert_eq!(x, vec!["Rust", "and", "fast", "is", "memory-efficient"]);
+    }
+
+    #[test]
+    fn sort_and_group_by_repeated_keys() {
+        let mut x = vec![
+            (3, 'a'),
+            (1, 'b'),
+            (2, 'c'),
+            (3, 'd'),
+            (1, 'e'),
+            (3, 'f'),
+            (5, 'g'),
+            (2, 'h'),
+        ];
+        let groups = sort_and_group_by(&mut x, &|v| v.0).unwrap();
+        assert_eq!(groups, vec![0..2, 2..4, 4..7, 7..8]);
+        for group in groups {
+            // グループ内のキーはすべて等しい
+            assert!(x[group.clone()].iter().all(|v| v.0 == x[group.start].0));
+        }
+        assert!(x[4..7].iter().all(|v| v.0 == 3));
+        // グループ内の要素は元の順序のまま並ぶ
+        assert_eq!(x[0..2], [(1, 'b'), (1, 'e')]);
+        assert_eq!(x[2..4], [(2, 'c'), (2, 'h')]);
+        assert_eq!(x[4..7], [(3, 'a'), (3, 'd'), (3, 'f')]);
+
+        let mut empty: Vec<(u32, char)> = Vec::new();
+        assert!(sort_and_group_by(&mut empty, &|v| v.0).is_err());
+        let mut one = vec![(1, 'a')];
+        let groups = sort_and_group_by(&mut one, &|v| v.0).unwrap();
+        assert_eq!(groups.len(), 1);
+        assert_eq!(groups[0], 0..1);
+    }
+
+    #[test]
+    fn sort_by_skipping_sorted_matches_unoptimized() {
+        let mut rng = Pcg64Mcg::from_seed([1; 16]);
+        for round in 0..200 {
+            let len = 1 << rng.gen_range(0, 11);
+            // ランダムな入力と、ほぼソート済みの入力を交互に試す
+            let mut input: Vec<(u32, usize)> =
+                (0..len).map(|i| (rng.gen_range(0, 16), i)).collect();
+            if round % 2 == 0 {
+                input.sort();
+                for _ in 0..rng.gen_range(0, 4) {
+                    let (a, b) = (rng.gen_range(0, len), rng.gen_range(0, len));
+                    input.swap(a, b);
+                }
+            }
+            // 等しいキーの要素の並びまで含めて、最適化しない場合と同じ結果になる
+            let comparator = |a: &(u32, usize), b: &(u32, usize)| a.0.cmp(&b.0);
+            let mut expected = input.clone();
+            assert!(sort_by(&mut expected, &comparator).is_ok());
+            let mut x = input.clone();
+            assert!(sort_by_skipping_sorted(&mut x, &comparator).is_ok());
+            assert_eq!(x, expected);
+        }
+    }
+
+    #[test]
+    fn sort_by_skipping_sorted_saves_comparisons() {
+        let mut x: Vec<u32> = (0..1024).collect();
+        x.swap(100, 101);
+        let mut y = x.clone();
+        let optimized = sort_by_skipping_sorted(&mut x, &|a, b| a.cmp(b)).unwrap();
+        let unoptimized = sort_by_counting(&mut y, &|a, b| a.cmp(b)).unwrap();
+        assert_eq!(x, y);
+        assert!(optimized.comparisons < unoptimized.comparisons);
+        assert!(sort_by_skipping_sorted(&mut x[..3], &|a, b| a.cmp(b)).is_err());
+    }
+
+    #[test]
+    fn sort_by_timed_large() {
+        let mut x = new_u32_vec(65536);
+        let duration = sort_by_timed(&mut x, &|a, b| a.cmp(b)).unwrap();
+        assert!(duration > Duration::from_nanos(0));
+        assert!(is_sorted_ascending(&x));
+        assert!(sort_by_timed(&mut x[..3], &|a, b| a.cmp(b)).is_err());
+    }
+
+    // 文字ごとの並び順を定義した照合表
+    struct CollationTable {
+        ranks: HashMap<char, usize>,
+    }
+
+    impl CollationTable {
+        fn new(order: &str) -> Self {
+            Self {
+                ranks: order.chars().enumerate().map(|(i, c)| (c, i)).collect(),
+            }
+        }
+
+        // 表にない文字は表にあるすべての文字より後ろに並べる
+        fn compare(&self, a: &str, b: &str) -> std::cmp::Ordering {
+            let rank = |c: char| self.ranks.get(&c).cloned().unwrap_or(usize::MAX);
+            a.chars().map(rank).cmp(b.chars().map(rank))
+        }
+    }
+
+    #[test]
+    fn sort_by_with_captured_context() {
+        // 母音を先に、子音を後に並べる独自の照合表
+        let table = CollationTable::new("aeioubcdfghjklmnpqrstvwxyz");
+        let mut x = vec!["bat", "apple", "cat", "egg", "ant", "ice", "dog", "up"];
+        // クロージャは照合表を参照で捕捉する
+        assert_eq!(sort_by(&mut x, &|a, b| table.compare(a, b)), Ok(()));
+        assert_eq!(
+            x,
+            vec!["ant", "apple", "egg", "ice", "up", "bat", "cat", "dog"]
+        );
+    }
+
+    #[test]
+    fn sort_students_by_two_comparators() {
+        let taro = Student::new("Taro", "Yamada", 16);
+        let hanako = Student::new("Hanako", "Yamada", 14);
+        let kyoko = Student::new("Kyoko", "Ito", 15);
+        let ryosuke = Student::new("Ryosuke", "Hayashi", 17);
+
+        let mut x = vec![&taro, &hanako, &kyoko, &ryosuke];
+        let expected = vec![&ryosuke, &kyoko, &hanako, &taro];
+
+        // まずlast_nameで、等しければfirst_nameで比較する
+        assert_eq!(
+            sort_by_two(&mut x, &|a, b| a.last_name.cmp(&b.last_name), &|a, b| a
+                .first_name
+                .cmp(&b.first_name)),
+            Ok(())
+        );
+        assert_eq!(x, expected);
+    }
+
+    #[test]
+    fn sort_adaptive_runs_nearly_sorted() {
+        let mut rng = Pcg64Mcg::from_seed([2; 16]);
+        for len in &[0, 1, 2, 7, 100, 1000, 5000] {
+            // ソート済みの配列の一部を入れ替え、さらに降順の区間を混ぜる
+            let mut input: Vec<u32> = (0..*len).collect();
+            for _ in 0..len / 50 {
+                let (a, b) = (
+                    rng.gen_range(0, *len as usize),
+                    rng.gen_range(0, *len as usize),
+                );
+                input.swap(a, b);
+            }
+            if *len >= 100 {
+                input[10..60].reverse();
+            }
+            let mut expected = input.clone();
+            expected.sort();
+
+            let mut x = input.clone();
+            sort_adaptive_runs(&mut x, &Ascending);
+            assert_eq!(x, expected);
+
+            let mut x = input.clone();
+            sort_adaptive_runs(&mut x, &Descending);
+            expected.reverse();
+            assert_eq!(x, expected);
+        }
+    }
+
+    #[test]
+    fn sort_adaptive_runs_random_and_duplicates() {
+        let mut x = new_u32_vec(10000);
+        x.extend(vec![5; 100]);
+        let mut expected = x.clone();
+        expected.sort();
+        sort_adaptive_runs(&mut x, &Ascending);
+        assert_eq!(x, expected);
+    }
+
+    #[test]
+    fn sort_by_iterative_matches_recursive() {
+        for &len in &[1, 2, 8, 1024, 65536] {
+            let input = new_u32_vec(len);
+            let mut expected = input.clone();
+            let recursive = sort_by_counting(&mut expected, &|a, b| b.cmp(a)).unwrap();
+            let mut x = input.clone();
+            let iterative = sort_by_iterative(&mut x, &|a, b| b.cmp(a)).unwrap();
+            assert_eq!(x, expected);
+            // 同じネットワークなので、比較と交換の回数も一致する
+            assert_eq!(iterative.comparisons, recursive.comparisons);
+            assert_eq!(iterative.swaps, recursive.swaps);
+        }
+        let mut x = vec![10, 30, 11];
+        assert!(sort_by_iterative(&mut x, &|a, b| a.cmp(b)).is_err());
+    }
+
+    #[test]
+    fn sort_arc_students_by_age() {
+        let taro = Arc::new(Student::new("Taro", "Yamada", 16));
+        let hanako = Arc::new(Student::new("Hanako", "Yamada", 14));
+        let kyoko = Arc::new(Student::new("Kyoko", "Ito", 15));
+        let ryosuke = Arc::new(Student::new("Ryosuke", "Hayashi", 17));
+
+        let mut x = vec![
+            Arc::clone(&taro),
+            Arc::clone(&hanako),
+            Arc::clone(&kyoko),
+            Arc::clone(&ryosuke),
+        ];
+        assert_eq!(sort_arc_by(&mut x, &|a, b| a.age.cmp(&b.age)), Ok(()));
+        assert!(Arc::ptr_eq(&x[0], &hanako));
+        assert!(Arc::ptr_eq(&x[1], &kyoko));
+        assert!(Arc::ptr_eq(&x[2], &taro));
+        assert!(Arc::ptr_eq(&x[3], &ryosuke));
+        // 参照カウントは変わらない
+        assert_eq!(Arc::strong_count(&taro), 2);
+    }
+
+    #[test]
+    fn sort_fixed_size_arrays() {
+        let mut x: [u32; 8] = [10, 30, 11, 20, 4, 330, 21, 110];
+        sort_array(&mut x, &Ascending);
+        assert_eq!(x, [4, 10, 11, 20, 21, 30, 110, 330]);
+        sort_array(&mut x, &Descending);
+        assert_eq!(x, [330, 110, 30, 21, 20, 11, 10, 4]);
+
+        let mut x: [u32; 16] = [16, 3, 9, 1, 14, 7, 2, 12, 5, 11, 8, 15, 4, 10, 6, 13];
+        sort_array(&mut x, &Ascending);
+        assert_eq!(x, [1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12, 13, 14, 15, 16]);
+    }
+
+    #[test]
+    fn sort_small_fixed_size_arrays() {
+        // 要素数4のネットワークは、0と1からなるすべての入力をソートできる（0-1原理）
+        for bits in 0..16u32 {
+            let mut x = [bits & 1, bits >> 1 & 1, bits >> 2 & 1, bits >> 3 & 1];
+            sort_array(&mut x, &Ascending);
+            assert!(is_sorted_ascending(&x));
+        }
+        let mut x = ["b", "a"];
+        sort_array(&mut x, &Ascending);
+        assert_eq!(x, ["a", "b"]);
+        let mut x = [1];
+        sort_array(&mut x, &Descending);
+        assert_eq!(x, [1]);
+    }
+
+    #[test]
+    fn compare_and_swap_at_orders_pair() {
+        let asc = |a: &i32, b: &i32| a.cmp(b);
+        let mut x = vec![3, 1, 2];
+        assert!(compare_and_swap_at(&mut x, 0, 1, &asc));
+        assert_eq!(x, vec![1, 3, 2]);
+        assert!(!compare_and_swap_at(&mut x, 0, 2, &asc));
+        assert!(compare_and_swap_at(&mut x, 1, 2, &asc));
+        assert_eq!(x, vec![1, 2, 3]);
+        // j < iのときも、小さい方がiに来る
+        assert!(compare_and_swap_at(&mut x, 2, 0, &asc));
+        assert_eq!(x, vec![3, 2, 1]);
+    }
+
+    #[test]
+    fn sort_with_runtime_direction() {
+        let input = new_u32_vec(1024);
+        for &(descending, ref order) in &[(false, Ascending), (true, Descending)] {
+            let mut expected = input.clone();
+            assert_eq!(sort(&mut expected, order), Ok(()));
+
+            let mut x = input.clone();
+            assert_eq!(sort_dir(&mut x, descending), Ok(()));
+            assert_eq!(x, expected);
+
+            let mut x = input.clone();
+            assert_eq!(sort_by_dir(&mut x, &|a, b| a.cmp(b), descending), Ok(()));
+            assert_eq!(x, expected);
+        }
+
+        let mut x = vec![10, 30, 11];
+        assert_eq!(sort_dir(&mut x, true), Err(SortError::not_power_of_two(3)));
+    }
+
+    #[test]
+    fn sort_paired_columns() {
+        let mut keys = vec![3, 1, 2, 0];
+        let mut values = vec!['d', 'b', 'c', 'a'];
+        assert_eq!(
+            sort_paired_by(&mut keys, &mut values, &|a, b| a.cmp(b)),
+            Ok(())
+        );
+        assert_eq!(keys, vec![0, 1, 2, 3]);
+        assert_eq!(values, vec!['a', 'b', 'c', 'd']);
+
+        // キーが等しいときは元の順序が保たれる
+        let mut keys = vec![2, 1, 2, 1];
+        let mut values = vec!["w", "x", "y", "z"];
+        assert_eq!(
+            sort_paired_by(&mut keys, &mut values, &|a, b| b.cmp(a)),
+            Ok(())
+        );
+        assert_eq!(keys, vec![2, 2, 1, 1]);
+        assert_eq!(values, vec!["w", "y", "x", "z"]);
+    }
+
+    #[test]
+    fn sort_paired_large() {
+        let mut keys = new_u32_vec(65536);
+        let mut values: Vec<u64> = keys.iter().map(|&k| k as u64 * 3).collect();
+        assert_eq!(
+            sort_paired_by(&mut keys, &mut values, &|a, b| a.cmp(b)),
+            Ok(())
+        );
+        assert!(is_sorted_ascending(&keys));
+        assert!(keys.iter().zip(&values).all(|(&k, &v)| v == k as u64 * 3));
+    }
+
+    #[test]
+    fn sort_paired_errors() {
+        let mut keys = vec![3, 1, 2, 0];
+        let mut values = vec!['d', 'b', 'c'];
+        assert_eq!(
+            sort_paired_by(&mut keys, &mut values, &|a, b| a.cmp(b)),
+            Err(SortError::LengthMismatch { keys: 4, values: 3 })
+        );
+        assert_eq!(keys, vec![3, 1, 2, 0]);
+
+        let mut keys = vec![3, 1, 2];
+        assert_eq!(
+            sort_paired_by(&mut keys, &mut values, &|a, b| a.cmp(b)),
+            Err(SortError::not_power_of_two(3))
+        );
+    }
+
+    #[test]
+    fn sort_with_runtime_chosen_comparator() {
+        struct ByAge;
+        impl Comparator<Student> for ByAge {
+            fn compare(&self, a: &Student, b: &Student) -> Ordering {
+                a.age.cmp(&b.age)
+            }
+        }
+
+        struct ByName;
+        impl Comparator<Student> for ByName {
+            fn compare(&self, a: &Student, b: &Student) -> Ordering {
+                a.last_name
+                    .cmp(&b.last_name)
+                    .then_with(|| a.first_name.cmp(&b.first_name))
+            }
+        }
+
+        let comparators: Vec<(&str, Box<dyn Comparator<Student> + Sync>)> =
+            vec![("age", Box::new(ByAge)), ("name", Box::new(ByName))];
+        for (name, comparator) in &comparators {
+            let mut x = vec![
+                Student::new("Taro", "Yamada", 16),
+                Student::new("Hanako", "Yamada", 14),
+                Student::new("Kyoko", "Ito", 15),
+                Student::new("Ryosuke", "Hayashi", 17),
+            ];
+            assert_eq!(sort_dyn(&mut x, comparator.as_ref()), Ok(()));
+            let first_names: Vec<&str> = x.iter().map(|s| s.first_name.as_str()).collect();
+            match *name {
+                "age" => assert_eq!(first_names, vec!["Hanako", "Kyoko", "Taro", "Ryosuke"]),
+                _ => assert_eq!(first_names, vec!["Ryosuke", "Kyoko", "Hanako", "Taro"]),
+            }
+        }
+
+        // クロージャもComparatorとして渡せる
+        let mut x = new_u32_vec(8192);
+        assert_eq!(sort_dyn(&mut x, &|a: &u32, b: &u32| b.cmp(a)), Ok(()));
+        assert!(is_sorted_descending(&x));
+    }
+
+    #[test]
+    fn sort_with_swept_thresholds() {
+        let input = new_u32_vec(1 << 16);
+        let mut expected = input.clone();
+        expected.sort_unstable();
+        for &threshold in &[256, 1024, 4096, 16384, 65536] {
+            let mut x = input.clone();
+            assert_eq!(
+                sort_by_with_threshold(&mut x, &|a, b| a.cmp(b), threshold),
+                Ok(())
+            );
+            assert_eq!(x, expected, "threshold: {}", threshold);
+        }
+        // しきい値が0でも（すべての再帰を並列にしても）正しくソートできる
+        let mut x = new_u32_vec(64);
+        assert_eq!(sort_by_with_threshold(&mut x, &|a, b| a.cmp(b), 0), Ok(()));
+        assert!(is_sorted_ascending(&x));
+    }
+
+    #[test]
+    fn sort_boxed_students_by_last_name() {
+        let taro = Student::new("Taro", "Yamada", 16);
+        let hanako = Student::new("Hanako", "Yamada", 14);
+        let kyoko = Student::new("Kyoko", "Ito", 15);
+        let ryosuke = Student::new("Ryosuke", "Hayashi", 17);
+        let mut x = vec![
+            Box::new(taro),
+            Box::new(kyoko),
+            Box::new(hanako),
+            Box::new(ryosuke),
+        ];
+        // ソートの前後でヒープ上の中身のアドレスは変わらない
+        let mut addresses: Vec<*const Student> = x.iter().map(|s| &**s as *const _).collect();
+
+        assert_eq!(
+            sort_boxed_by(&mut x, &|a: &Student, b: &Student| a
+                .last_name
+                .cmp(&b.last_name)),
+            Ok(())
+        );
+        let last_names: Vec<&str> = x.iter().map(|s| s.last_name.as_str()).collect();
+        assert_eq!(last_names, vec!["Hayashi", "Ito", "Yamada", "Yamada"]);
+
+        let mut sorted_addresses: Vec<*const Student> =
+            x.iter().map(|s| &**s as *const _).collect();
+        addresses.sort();
+        sorted_addresses.sort();
+        assert_eq!(addresses, sorted_addresses);
+    }
+
+    #[test]
+    fn sort_boxed_unsized() {
+        let mut x: Vec<Box<str>> = vec![
+            "cherry".into(),
+            "apple".into(),
+            "durian".into(),
+            "banana".into(),
+        ];
+        assert_eq!(sort_boxed_by(&mut x, &|a: &str, b: &str| b.cmp(a)), Ok(()));
+        assert_eq!(
+            x,
+            vec![
+                "durian".into(),
+                "cherry".into(),
+                "banana".into(),
+                "apple".into()
+            ] as Vec<Box<str>>
+        );
+    }
+
+    #[test]
+    fn presorted_descending_inputs_do_minimal_work() {
+        let descending = |a: &u32, b: &u32| b.cmp(a);
+
+        // 降順に並んだ入力の降順ソートは、確認の比較（n - 1回）だけで何もしない
+        let mut x: Vec<u32> = (0..1024).rev().collect();
+        let metrics = sort_by_counting(&mut x, &descending).unwrap();
+        assert_eq!(
+            metrics,
+            SortMetrics {
+                comparisons: 1023,
+                ..SortMetrics::default()
+            }
+        );
+        assert!(is_sorted_descending(&x));
+
+        // 昇順に並んだ入力の降順ソートは反転するだけ（交換はn / 2回）
+        // 確認の比較は、降順かどうかの1回（最初の2要素で打ち切る）と、昇順かどうかのn - 1回
+        let mut x: Vec<u32> = (0..1024).collect();
+        let metrics = sort_by_counting(&mut x, &descending).unwrap();
+        assert_eq!(metrics.comparisons, 1024);
+        assert_eq!(metrics.swaps, 512);
+        assert_eq!(x, (0..1024).rev().collect::<Vec<u32>>());
+
+        // 等しい値を含んでいても同じように扱う
+        let mut x = vec![1, 1, 2, 3, 3, 3, 4, 9];
+        let metrics = sort_by_counting(&mut x, &descending).unwrap();
+        assert_eq!(metrics.comparisons, 2 + 7);
+        assert_eq!(metrics.swaps, 4);
+        assert_eq!(x, vec![9, 4, 3, 3, 3, 2, 1, 1]);
+    }
+
+    #[test]
+    fn presorted_ascending_inputs_do_minimal_work() {
+        let ascending = |a: &u32, b: &u32| a.cmp(b);
+
+        let mut x: Vec<u32> = (0..1024).collect();
+        let metrics = sort_by_counting(&mut x, &ascending).unwrap();
+        assert_eq!(
+            metrics,
+            SortMetrics {
+                comparisons: 1023,
+                ..SortMetrics::default()
+            }
+        );
+
+        let mut x: Vec<u32> = (0..1024).rev().collect();
+        let metrics = sort_by_counting(&mut x, &ascending).unwrap();
+        assert_eq!(metrics.comparisons, 1024);
+        assert_eq!(metrics.swaps, 512);
+        assert!(is_sorted_ascending(&x));
+
+        // すべて等しい値なら、確認の比較だけで何もしない
+        let mut x = vec![7; 64];
+        assert_eq!(
+            sort_by_counting(&mut x, &ascending).unwrap(),
+            SortMetrics {
+                comparisons: 63,
+                ..SortMetrics::default()
+            }
+        );
+
+        // 要素数2以下では確認せず、ネットワークの比較をそのまま行う
+        let mut x = vec![1, 2];
+        assert_eq!(sort_by_counting(&mut x, &ascending).unwrap().comparisons, 1);
+    }
+
+    #[test]
+    fn apply_permutation_in_place_cases() {
+        let permute = |array: &[u32], permutation: &[usize]| {
+            let mut x = array.to_vec();
+            let mut p = permutation.to_vec();
+            apply_permutation_in_place(&mut x, &mut p);
+            // 処理後はすべての位置に印（p[i] == i）が付いている
+            assert!(p.iter().enumerate().all(|(i, &j)| i == j));
+            x
+        };
+        let x = [10, 20, 30, 40, 50, 60];
+        // 恒等置換
+        assert_eq!(permute(&x, &[0, 1, 2, 3, 4, 5]), x.to_vec());
+        // 1つの長い巡回置換
+        assert_eq!(
+            permute(&x, &[1, 2, 3, 4, 5, 0]),
+            vec![20, 30, 40, 50, 60, 10]
+        );
+        assert_eq!(
+            permute(&x, &[5, 0, 1, 2, 3, 4]),
+            vec![60, 10, 20, 30, 40, 50]
+        );
+        // 互換と固定点の組み合わせ
+        assert_eq!(
+            permute(&x, &[1, 0, 2, 5, 4, 3]),
+            vec![20, 10, 30, 60, 50, 40]
+        );
+        // 空の配列
+        assert_eq!(permute(&[], &[]), Vec::<u32>::new());
+
+        // apply_permutationと同じ結果になる
+        let mut rng = Pcg64Mcg::from_seed([5; 16]);
+        let values = new_u32_vec(1000);
+        let mut permutation: Vec<usize> = (0..1000).collect();
+        for i in (1..1000).rev() {
+            permutation.swap(i, rng.gen_range(0, i + 1));
+        }
+        let mut expected = values.clone();
+        apply_permutation(&mut expected, &mut permutation.clone());
+        assert_eq!(permute(&values, &permutation), expected);
+    }
+
+    #[test]
+    fn sort_keys_copied_matches_sort_keys() {
+        let input: Vec<(u8, u32)> = new_u32_vec(4096)
+            .into_iter()
+            .map(|v| ((v % 13) as u8, v))
+            .collect();
+        let mut expected = input.clone();
+        assert_eq!(sort_keys(&mut expected, &|&(k, _)| k), Ok(()));
+        let mut x = input;
+        assert_eq!(sort_keys_copied(&mut x, &|&(k, _)| k), Ok(()));
+        assert_eq!(x, expected);
+        assert!(sort_keys_copied(&mut x[..3], &|&(k, _)| k).is_err());
+    }
+
+    #[test]
+    fn sort_streaming_sends_sorted_elements() {
+        let input = new_u32_vec(10000);
+        let received: Vec<u32> = sort_streaming(input.clone(), Ascending).iter().collect();
+        assert!(is_sorted_ascending(&received));
+        let mut expected = input;
+        expected.sort_unstable();
+        assert_eq!(received, expected);
+
+        let received: Vec<String> = sort_streaming(
+            vec!["b".to_string(), "c".to_string(), "a".to_string()],
+            Descending,
+        )
+        .iter()
+        .collect();
+        assert_eq!(received, vec!["c", "b", "a"]);
+
+        assert_eq!(
+            sort_streaming(Vec::<u32>::new(), Ascending).iter().count(),
+            0
+        );
+
+        // 2つに分けた長さが異なる入力や、要素が1つの入力も正しくマージする
+        for &len in &[1, 2, 5, 1023] {
+            let input = new_u32_vec(len);
+            let received: Vec<u32> = sort_streaming(input.clone(), Descending).iter().collect();
+            let mut expected = input;
+            expected.sort_unstable_by(|a, b| b.cmp(a));
+            assert_eq!(received, expected);
+        }
+
+        // 途中で受け取るのをやめても、ワーカースレッドは送るのをやめて終了する
+        let receiver = sort_streaming(new_u32_vec(1024), Ascending);
+        assert!(receiver.recv().is_ok());
+        drop(receiver);
+    }
+
+    #[test]
+    fn sort_by_scoped_large() {
+        let mut x = new_u32_vec(1 << 16);
+        assert_eq!(sort_by_scoped(&mut x, &|a, b| a.cmp(b)), Ok(()));
+        assert!(is_sorted_ascending(&x));
+        assert!(sort_by_scoped(&mut x[..3], &|a, b| a.cmp(b)).is_err());
+    }
+
+    #[test]
+    fn sort_by_scoped_limits_threads() {
+        use std::sync::atomic::{AtomicUsize, Ordering as AtomicOrdering};
+
+        assert_eq!(scoped_spawn_depth(1), 0);
+        assert_eq!(scoped_spawn_depth(4), 2);
+        assert_eq!(scoped_spawn_depth(6), 3);
+
+        // 同時に比較を行っているスレッドの数の最大値を記録しながらソートする
+        let max_concurrent = |depth: u32, is_asc: bool| {
+            let active = AtomicUsize::new(0);
+            let max = AtomicUsize::new(0);
+            let mut x = new_u32_vec(1 << 14);
+            do_sort_scoped(
+                &mut x,
+                is_asc,
+                &|a: &u32, b: &u32| {
+                    let now = active.fetch_add(1, AtomicOrdering::SeqCst) + 1;
+                    max.fetch_max(now, AtomicOrdering::SeqCst);
+                    let ordering = a.cmp(b);
+                    active.fetch_sub(1, AtomicOrdering::SeqCst);
+                    ordering
+                },
+                16,
+                depth,
+            );
+            assert!(is_sorted_ascending(&x) == is_asc);
+            max.into_inner()
+        };
+        // スレッドを生成する段数が2なら、同時に動くスレッドは高々4つになる
+        assert!(max_concurrent(2, true) <= 4);
+        // 段数が0なら、呼び出したスレッドだけで処理する
+        assert_eq!(max_concurrent(0, false), 1);
+    }
+
+    #[test]
+    fn sort_events_by_projected_timestamp() {
+        #[derive(Debug, PartialEq)]
+        enum Event {
+            Click { t: u64 },
+            Key { t: u64 },
+        }
+
+        let mut events = vec![
+            Event::Key { t: 30 },
+            Event::Click { t: 10 },
+            Event::Click { t: 40 },
+            Event::Key { t: 20 },
+            Event::Click { t: 20 },
+            Event::Key { t: 5 },
+            Event::Click { t: 50 },
+            Event::Key { t: 45 },
+        ];
+        let timestamp = |e: &Event| match *e {
+            Event::Click { t } | Event::Key { t } => t,
+        };
+        assert_eq!(sort_by_projection(&mut events, &timestamp), Ok(()));
+        assert_eq!(
+            events,
+            vec![
+                Event::Key { t: 5 },
+                Event::Click { t: 10 },
+                // 時刻が等しいイベントは元の順序のまま
+                Event::Key { t: 20 },
+                Event::Click { t: 20 },
+                Event::Key { t: 30 },
+                Event::Click { t: 40 },
+                Event::Key { t: 45 },
+                Event::Click { t: 50 },
+            ]
+        );
+    }
+
+    #[cfg(feature = "metrics")]
+    #[test]
+    fn sort_emits_metrics() {
+        use metrics::{
+            Counter, Gauge, Histogram, Key, KeyName, Metadata, Recorder, SharedString, Unit,
+        };
+        use std::sync::Mutex;
+
+        // 登録された指標の名前を記録するだけのレコーダー
+        #[derive(Default)]
+        struct NameRecorder {
+            names: Mutex<Vec<String>>,
+        }
+
+        impl Recorder for NameRecorder {
+            fn describe_counter(&self, _: KeyName, _: Option<Unit>, _: SharedString) {}
+            fn describe_gauge(&self, _: KeyName, _: Option<Unit>, _: SharedString) {}
+            fn describe_histogram(&self, _: KeyName, _: Option<Unit>, _: SharedString) {}
+
+            fn register_counter(&self, key: &Key, _: &Metadata<'_>) -> Counter {
+                self.names.lock().unwrap().push(key.name().to_string());
+                Counter::noop()
+            }
+
+            fn register_gauge(&self, key: &Key, _: &Metadata<'_>) -> Gauge {
+                self.names.lock().unwrap().push(key.name().to_string());
+                Gauge::noop()
+            }
+
+            fn register_histogram(&self, key: &Key, _: &Metadata<'_>) -> Histogram {
+                self.names.lock().unwrap().push(key.name().to_string());
+                Histogram::noop()
+            }
+        }
+
+        let recorder = NameRecorder::default();
+        metrics::with_local_recorder(&recorder, || {
+            let mut x = new_u32_vec(1024);
+            assert_eq!(sort(&mut x, &Ascending), Ok(()));
+            let mut x = new_u32_vec(16);
+            assert_eq!(sort(&mut x, &Descending), Ok(()));
+        });
+        let names = recorder.names.lock().unwrap();
+        let sorts = names
+            .iter()
+            .filter(|n| *n == "bitonic_sorter.sorts")
+            .count();
+        assert_eq!(sorts, 2);
+        assert!(names.iter().any(|n| n == "bitonic_sorter.comparisons"));
+        assert!(names.iter().any(|n| n == "bitonic_sorter.duration_seconds"));
+    }
+
+    #[test]
+    fn sort_from_heap_matches_into_sorted_vec() {
+        let heap: BinaryHeap<u32> = new_u32_vec(3000).into_iter().collect();
+        assert_eq!(
+            sort_from_heap(heap.clone(), &Ascending),
+            heap.clone().into_sorted_vec()
+        );
+
+        let mut expected = heap.clone().into_sorted_vec();
+        expected.reverse();
+        assert_eq!(sort_from_heap(heap, &Descending), expected);
+
+        assert!(sort_from_heap(BinaryHeap::<u32>::new(), &Ascending).is_empty());
+    }
+
+    #[test]
+    fn network_comparison_sequence_for_8_elements() {
+        // do_sortとsub_sortの構造が変わると、この比較の順序も変わる
+        let golden: Vec<(usize, usize)> = vec![
+            // 前半（0..4）を昇順に
+            (0, 1),
+            (2, 3),
+            (0, 2),
+            (1, 3),
+            (0, 1),
+            (2, 3),
+            // 後半（4..8）を降順に
+            (4, 5),
+            (6, 7),
+            (4, 6),
+            (5, 7),
+            (4, 5),
+            (6, 7),
+            // バイトニック列全体をマージする
+            (0, 4),
+            (1, 5),
+            (2, 6),
+            (3, 7),
+            (0, 2),
+            (1, 3),
+            (0, 1),
+            (2, 3),
+            (4, 6),
+            (5, 7),
+            (4, 5),
+            (6, 7),
+        ];
+        let sequence = std::sync::Mutex::new(Vec::new());
+        let mut x = vec![10, 30, 11, 20, 4, 330, 21, 110];
+        let metrics = sort_by_observed(&mut x, &|a, b| a.cmp(b), &|i, j| {
+            sequence.lock().unwrap().push((i, j))
+        })
+        .unwrap();
+        assert_eq!(x, vec![4, 10, 11, 20, 21, 30, 110, 330]);
+        assert_eq!(sequence.into_inner().unwrap(), golden);
+        assert_eq!(metrics.comparisons, 24);
+
+        // ソート済みの入力でも同じ順序で比較する
+        let sequence = std::sync::Mutex::new(Vec::new());
+        let mut x: Vec<u32> = (0..8).collect();
+        sort_by_observed(&mut x, &|a, b| a.cmp(b), &|i, j| {
+            sequence.lock().unwrap().push((i, j))
+        })
+        .unwrap();
+        assert_eq!(sequence.into_inner().unwrap(), golden);
+    }
+
+    #[test]
+    fn argsort_with_inverse_round_trip() {
+        let input = vec!["pear", "apple", "fig", "kiwi", "apple", "banana"];
+        let (perm, inverse) = argsort_with_inverse_by(&input, &|a, b| a.cmp(b));
+        assert_eq!(perm, vec![1, 4, 5, 2, 3, 0]);
+        assert!((0..input.len()).all(|i| inverse[perm[i]] == i));
+
+        let sorted: Vec<&str> = perm.iter().map(|&i| input[i]).collect();
+        assert!(is_sorted_ascending(&sorted));
+        // 逆置換でソート済みの配列を元の順序に戻す
+        let restored: Vec<&str> = inverse.iter().map(|&i| sorted[i]).collect();
+        assert_eq!(restored, input);
+
+        let input = new_u32_vec(1000);
+        let (perm, inverse) = argsort_with_inverse_by(&input, &|a, b| b.cmp(a));
+        let mut sorted: Vec<u32> = perm.iter().map(|&i| input[i]).collect();
+        assert!(is_sorted_descending(&sorted));
+        apply_permutation(&mut sorted, &mut inverse.clone());
+        assert_eq!(sorted, input);
+
+        assert_eq!(
+            argsort_with_inverse_by(&[] as &[u32], &|a, b| a.cmp(b)),
+            (vec![], vec![])
+        );
+    }
+
+    #[test]
+    fn sort_by_controlled_completes() {
+        let mut x = new_u32_vec(1 << 16);
+        let ctrl = SortControl::with_deadline(Instant::now() + Duration::from_secs(3600));
+        assert_eq!(sort_by_controlled(&mut x, &|a, b| a.cmp(b), &ctrl), Ok(()));
+        assert!(is_sorted_ascending(&x));
+
+        let mut x = new_u32_vec(16);
+        assert_eq!(
+            sort_by_controlled(&mut x, &|a, b| b.cmp(a), &SortControl::new()),
+            Ok(())
+        );
+        assert!(is_sorted_descending(&x));
+        assert_eq!(
+            sort_by_controlled(&mut x[..3], &|a, b| a.cmp(b), &SortControl::new()),
+            Err(SortError::not_power_of_two(3))
+        );
+    }
+
+    #[test]
+    fn sort_by_controlled_cancelled() {
+        let input = new_u32_vec(1 << 16);
+        let mut x = input.clone();
+        let ctrl = SortControl::new();
+        ctrl.cancel();
+        assert_eq!(
+            sort_by_controlled(&mut x, &|a, b| a.cmp(b), &ctrl),
+            Err(SortError::Cancelled)
+        );
+
+        // ソートの途中で取り消す（1000回目の比較で取り消しを指示する）
+        let mut x = input.clone();
+        let ctrl = SortControl::new();
+        let comparisons = std::sync::atomic::AtomicUsize::new(0);
+        let result = sort_by_controlled(
+            &mut x,
+            &|a: &u32, b: &u32| {
+                if comparisons.fetch_add(1, std::sync::atomic::Ordering::Relaxed) == 1000 {
+                    ctrl.cancel();
+                }
+                a.cmp(b)
+            },
+            &ctrl,
+        );
+        assert_eq!(result, Err(SortError::Cancelled));
+        // 打ち切られても要素は失われない
+        assert!(is_permutation_of(&x, &input));
+    }
+
+    #[test]
+    fn sort_by_controlled_timed_out() {
+        let mut x = new_u32_vec(1 << 16);
+        let ctrl = SortControl::with_deadline(Instant::now());
+        assert_eq!(
+            sort_by_controlled(&mut x, &|a, b| a.cmp(b), &ctrl),
+            Err(SortError::TimedOut)
+        );
+
+        // 比較に時間がかかり、途中で期限を過ぎる
+        let mut x = new_u32_vec(1 << 12);
+        let ctrl = SortControl::with_deadline(Instant::now() + Duration::from_millis(20));
+        let result = sort_by_controlled(
+            &mut x,
+            &|a: &u32, b: &u32| {
+                std::thread::sleep(Duration::from_micros(10));
+                a.cmp(b)
+            },
+            &ctrl,
+        );
+        assert_eq!(result, Err(SortError::TimedOut));
+
+        // 取り消しと期限切れが両方起きていれば、取り消しを優先する
+        ctrl.cancel();
+        assert_eq!(
+            sort_by_controlled(&mut x, &|a, b| a.cmp(b), &ctrl),
+            Err(SortError::Cancelled)
+        );
+    }
+
+    #[test]
+    fn sort_wrapped_deque() {
+        // 先頭から取り出して末尾に追加し、要素がリングバッファの端をまたぐようにする
+        let mut deque: VecDeque<String> = VecDeque::with_capacity(8);
+        for word in &["delta", "alpha", "echo", "golf", "bravo", "hotel"] {
+            deque.push_back(word.to_string());
+        }
+        deque.pop_front();
+        deque.pop_front();
+        for word in &["charlie", "foxtrot", "india", "alpha"] {
+            deque.push_back(word.to_string());
+        }
+        assert!(!deque.as_slices().1.is_empty());
+        assert_eq!(deque.len(), 8);
+
+        assert_eq!(sort_deque(&mut deque, &Ascending), Ok(()));
+        let sorted: Vec<&str> = deque.iter().map(|s| s.as_str()).collect();
+        assert_eq!(
+            sorted,
+            vec!["alpha", "bravo", "charlie", "echo", "foxtrot", "golf", "hotel", "india"]
+        );
+
+        // ソート後も両端への追加と取り出しができる
+        assert_eq!(deque.pop_front().as_deref(), Some("alpha"));
+        deque.push_back("juliett".to_string());
+        assert_eq!(sort_deque(&mut deque, &Descending), Ok(()));
+        assert_eq!(deque.front().map(|s| s.as_str()), Some("juliett"));
+
+        deque.pop_back();
+        assert_eq!(
+            sort_deque(&mut deque, &Ascending),
+            Err(SortError::not_power_of_two(7))
+        );
+    }
+
+    #[test]
+    fn huge_reversed_input_is_only_reversed() {
+        // 逆順の判定が大きな入力でも働き、ネットワークを実行しないことを確かめる
+        let len = 1 << 20;
+        let mut x: Vec<u32> = (0..len as u32).rev().collect();
+        let metrics = sort_by_counting(&mut x, &|a, b| a.cmp(b)).unwrap();
+        assert_eq!(
+            metrics,
+            SortMetrics {
+                comparisons: len as u64,
+                swaps: len as u64 / 2,
+                ..SortMetrics::default()
+            }
+        );
+        assert!(is_sorted_ascending(&x));
+
+        // 先頭の2要素だけが逆順でない入力では判定が働かず、ネットワーク全体を実行する
+        let len = 1 << 16;
+        let mut x: Vec<u32> = (0..len as u32).rev().collect();
+        x.swap(0, 1);
+        let metrics = sort_by_counting(&mut x, &|a, b| a.cmp(b)).unwrap();
+        assert_eq!(metrics.comparisons, (len as u64 / 4) * 16 * 17);
+        assert!(is_sorted_ascending(&x));
+    }
+
+    #[test]
+    fn sort_by_precomputed_keys() {
+        let mut names = vec!["carol", "alice", "dave", "bob"];
+        let scores = vec![72, 95, 72, 88];
+        assert_eq!(
+            sort_by_external_keys(&mut names, &scores, &Descending),
+            Ok(())
+        );
+        // 同じ点数のcarolとdaveは元の順序のまま
+        assert_eq!(names, vec!["alice", "bob", "carol", "dave"]);
+        assert_eq!(scores, vec![72, 95, 72, 88]);
+
+        assert_eq!(
+            sort_by_external_keys(&mut names, &[3, 2, 1, 0], &Ascending),
+            Ok(())
+        );
+        assert_eq!(names, vec!["dave", "carol", "bob", "alice"]);
+
+        assert_eq!(
+            sort_by_external_keys(&mut names, &[1, 2], &Ascending),
+            Err(SortError::LengthMismatch { keys: 2, values: 4 })
+        );
+        assert_eq!(
+            sort_by_external_keys(&mut names[..3], &[1, 2, 3], &Ascending),
+            Err(SortError::not_power_of_two(3))
+        );
+    }
+
+    #[test]
+    fn sort_soa_matches_paired_sort() {
+        let keys = new_u32_vec(4096);
+        let payloads: Vec<String> = keys.iter().map(|k| format!("payload {}", k)).collect();
+
+        let (mut expected_keys, mut expected_payloads) = (keys.clone(), payloads.clone());
+        assert_eq!(
+            sort_paired_by(&mut expected_keys, &mut expected_payloads, &|a, b| b.cmp(a)),
+            Ok(())
+        );
+        let (mut k, mut p) = (keys.clone(), payloads);
+        assert_eq!(sort_soa_by(&mut k, &mut p, &|a, b| b.cmp(a)), Ok(()));
+        assert_eq!(k, expected_keys);
+        assert_eq!(p, expected_payloads);
+
+        let mut short = vec![String::new(); 3];
+        assert_eq!(
+            sort_soa_by(&mut k, &mut short, &|a, b| a.cmp(b)),
+            Err(SortError::LengthMismatch {
+                keys: 4096,
+                values: 3
+            })
+        );
+    }
+
+    #[test]
+    fn apply_permutation_by_moving_cases() {
+        for permutation in &[
+            vec![0, 1, 2, 3, 4, 5],
+            vec![1, 2, 3, 4, 5, 0],
+            vec![1, 0, 2, 5, 4, 3],
+        ] {
+            let input: Vec<String> = (0..6).map(|i| i.to_string()).collect();
+            let mut expected = input.clone();
+            apply_permutation(&mut expected, &mut permutation.clone());
+            let mut x = input;
+            let mut p = permutation.clone();
+            apply_permutation_by_moving(&mut x, &mut p);
+            assert_eq!(x, expected);
+            assert!(p.iter().enumerate().all(|(i, &j)| i == j));
+        }
+    }
+
+    #[test]
+    fn parse_threshold_values() {
+        assert_eq!(parse_threshold(None), Ok(4096));
+        assert_eq!(parse_threshold(Some("1024")), Ok(1024));
+        assert_eq!(parse_threshold(Some(" 65536\n")), Ok(65536));
+        assert_eq!(parse_threshold(Some("0")), Err("0"));
+        assert_eq!(parse_threshold(Some("-1")), Err("-1"));
+        assert_eq!(parse_threshold(Some("4k")), Err("4k"));
+        assert_eq!(parse_threshold(Some("")), Err(""));
+    }
+
+    #[test]
+    fn sort_mixed_cow_strings() {
+        let source = String::from("kiwi apple");
+        let mut words: Vec<Cow<str>> = vec![
+            Cow::Borrowed(&source[..4]),
+            Cow::Owned("banana".to_string()),
+            Cow::Borrowed(&source[5..]),
+            Cow::Owned("cherry".to_string()),
+        ];
+        assert_eq!(sort_cow_str(&mut words, &Ascending), Ok(()));
+        assert_eq!(words, vec!["apple", "banana", "cherry", "kiwi"]);
+        // 借用と所有の区別が保たれ、借用は元の文字列を指したまま
+        assert!(matches!(words[0], Cow::Borrowed(s) if std::ptr::eq(s, &source[5..])));
+        assert!(matches!(words[1], Cow::Owned(_)));
+        assert!(matches!(words[2], Cow::Owned(_)));
+        assert!(matches!(words[3], Cow::Borrowed(_)));
+
+        assert_eq!(sort_cow_str(&mut words, &Descending), Ok(()));
+        assert_eq!(words, vec!["kiwi", "cherry", "banana", "apple"]);
+        assert!(matches!(words[0], Cow::Borrowed(_)));
+        assert!(sort_cow_str(&mut words[..3], &Ascending).is_err());
+    }
+
+    #[test]
+    fn ranks_by_each_method() {
+        let input = vec![30, 10, 20, 20, 50, 10, 20];
+        let asc = |a: &u32, b: &u32| a.cmp(b);
+        assert_eq!(
+            ranks_by(&input, &asc, RankMethod::Dense),
+            vec![3, 1, 2, 2, 4, 1, 2]
+        );
+        assert_eq!(
+            ranks_by(&input, &asc, RankMethod::Competition),
+            vec![6, 1, 3, 3, 7, 1, 3]
+        );
+        // 等しい要素は元の順序で順位が付く
+        assert_eq!(
+            ranks_by(&input, &asc, RankMethod::Ordinal),
+            vec![6, 1, 3, 4, 7, 2, 5]
+        );
+
+        let desc = |a: &u32, b: &u32| b.cmp(a);
+        assert_eq!(
+            ranks_by(&input, &desc, RankMethod::Competition),
+            vec![2, 6, 3, 3, 1, 6, 3]
+        );
+
+        assert_eq!(
+            ranks_by(&[] as &[u32], &asc, RankMethod::Dense),
+            Vec::<usize>::new()
+        );
+    }
+
+    #[test]
+    fn sort_by_with_moved_mask_marks_changes() {
+        let mut x = vec![3, 1, 2, 4, 4, 6, 7, 5];
+        let mask = sort_by_with_moved_mask(&mut x, &|a, b| a.cmp(b)).unwrap();
+        assert_eq!(x, vec![1, 2, 3, 4, 4, 5, 6, 7]);
+        assert_eq!(mask, vec![true, true, true, false, false, true, true, true]);
+
+        // ソート済みなら何も動かない
+        let mut x = vec![1, 2, 3, 4];
+        let mask = sort_by_with_moved_mask(&mut x, &|a, b| a.cmp(b)).unwrap();
+        assert_eq!(mask, vec![false; 4]);
+
+        let mut x = vec![1, 2, 3];
+        assert_eq!(
+            sort_by_with_moved_mask(&mut x, &|a, b| a.cmp(b)),
+            Err(SortError::not_power_of_two(3))
+        );
+        assert_eq!(x, vec![1, 2, 3]);
+    }
+
+    #[test]
+    fn sort_by_with_cost_hint_parallelizes_expensive_comparisons() {
+        let input = new_u32_vec(512);
+        assert!(input.len() < parallel_threshold::<u32>());
+
+        // 安い比較ではしきい値より小さな配列を並列に処理しない
+        let mut x = input.clone();
+        let metrics = sort_by_with_cost_hint(&mut x, &|a, b| a.cmp(b), 1).unwrap();
+        assert!(is_sorted_ascending(&x));
+        assert_eq!(metrics.parallel_tasks, 0);
+
+        // 高いコストを指定すれば、同じ大きさの配列でも並列に処理する
+        let mut y = input.clone();
+        let metrics = sort_by_with_cost_hint(&mut y, &|a, b| a.cmp(b), 1000).unwrap();
+        assert_eq!(y, x);
+        assert!(metrics.parallel_tasks > 0);
+
+        let mut z = input;
+        let metrics = sort_by_with_cost_hint(&mut z, &|a, b| b.cmp(a), 0).unwrap();
+        assert!(is_sorted_descending(&z));
+        assert_eq!(metrics.parallel_tasks, 0);
+
+        assert!(sort_by_with_cost_hint(&mut z[..3], &|a, b| a.cmp(b), 10).is_err());
+    }
+
+    #[test]
+    fn lazy_sorted_by_yields_smallest_first() {
+        let input = new_u32_vec(100_000);
+        let calls = std::sync::atomic::AtomicUsize::new(0);
+        let first: Vec<u32> = lazy_sorted_by(input.clone(), |a: &u32, b: &u32| {
+            calls.fetch_add(1, std::sync::atomic::Ordering::Relaxed);
+            a.cmp(b)
+        })
+        .take(3)
+        .collect();
+        let mut expected = input.clone();
+        expected.sort();
+        assert_eq!(first, expected[..3]);
+        // ヒープを作るのは2n回以下の比較で済み、全体のソートよりずっと少ない
+        assert!(calls.load(std::sync::atomic::Ordering::Relaxed) < 2 * input.len() + 200);
+
+        let all: Vec<u32> = lazy_sorted_by(input, |a: &u32, b: &u32| b.cmp(a)).collect();
+        expected.reverse();
+        assert_eq!(all, expected);
+
+        let mut iter = lazy_sorted_by(vec![3, 1, 2], |a: &i32, b: &i32| a.cmp(b));
+        assert_eq!(iter.size_hint(), (3, Some(3)));
+        assert_eq!(iter.next(), Some(1));
+        assert_eq!(iter.size_hint(), (2, Some(2)));
+        assert_eq!(
+            lazy_sorted_by(Vec::<u32>::new(), |a, b| a.cmp(b)).next(),
+            None
+        );
+    }
+
+    #[test]
+    fn sort_keys_stable_with_parallel_extraction() {
+        // キーの等しい要素が多い大きな配列を、複数のスレッドでキーを取り出しながらソートする
+        let input: Vec<(u32, usize)> = new_u32_vec(1 << 16)
+            .into_iter()
+            .enumerate()
+            .map(|(i, v)| (v % 16, i))
+            .collect();
+        let pool = rayon::ThreadPoolBuilder::new()
+            .num_threads(4)
+            .build()
+            .unwrap();
+        for _ in 0..4 {
+            let mut x = input.clone();
+            assert_eq!(pool.install(|| sort_keys(&mut x, &|&(k, _)| k)), Ok(()));
+            // キーが等しい要素は元の位置の順に並ぶ
+            assert!(x
+                .windows(2)
+                .all(|w| w[0].0 < w[1].0 || (w[0].0 == w[1].0 && w[0].1 < w[1].1)));
+
+            let mut y = input.clone();
+            assert_eq!(
+                pool.install(|| sort_keys_copied(&mut y, &|&(k, _)| Reverse(k))),
+                Ok(())
+            );
+            assert!(y
+                .windows(2)
+                .all(|w| w[0].0 > w[1].0 || (w[0].0 == w[1].0 && w[0].1 < w[1].1)));
+        }
+    }
+
+    #[test]
+    fn sort_durations() {
+        let input = new_duration_vec(1 << 12);
+        let mut x = input.clone();
+        assert_eq!(sort(&mut x, &Ascending), Ok(()));
+        assert!(is_sorted_ascending(&x));
+        assert!(is_permutation_of(&x, &input));
+        let mut x = input;
+        assert_eq!(sort(&mut x, &Descending), Ok(()));
+        assert!(is_sorted_descending(&x));
+
+        // Debugの文字列（"10s" < "2s" < "999ms"）ではなく、時間の長さで比較される
+        let mut x = vec![
+            Duration::from_secs(2),
+            Duration::from_millis(999),
+            Duration::from_secs(10),
+            Duration::from_nanos(1),
+        ];
         assert_eq!(sort(&mut x, &Ascending), Ok(()));
         assert_eq!(
             x,
             vec![
-                "GC",
-                "Rust",
-                "and",
-                "fast",
-                "is",
-                "memory-efficient",
-                "no",
-                "with"
+                Duration::from_nanos(1),
+                Duration::from_millis(999),
+                Duration::from_secs(2),
+                Duration::from_secs(10),
             ]
         );
     }
 
     #[test]
-    fn sort_str_descending() {
+    fn sort_by_instant_keys() {
+        // 基準の時刻からのずれで、順序の分かっているInstantを作る
+        let base = Instant::now();
+        let offsets = new_duration_vec(1024);
+        let mut events: Vec<(Instant, usize)> = offsets
+            .iter()
+            .enumerate()
+            .map(|(i, &offset)| (base + offset, i))
+            .collect();
+        assert_eq!(sort_keys(&mut events, &|&(at, _)| at), Ok(()));
+        assert!(events.windows(2).all(|w| w[0].0 <= w[1].0));
+        let mut expected = offsets;
+        expected.sort();
+        let actual: Vec<Duration> = events.iter().map(|&(at, _)| at - base).collect();
+        assert_eq!(actual, expected);
+    }
+
+    #[cfg(all(feature = "debug-validate", debug_assertions))]
+    #[test]
+    #[should_panic(expected = "comparator is not a strict weak ordering")]
+    fn debug_validate_rejects_inconsistent_comparator() {
+        // じゃんけんのように3で割った余りで勝ち負けを決める比較は、推移律を満たさない
+        // （0 < 1、1 < 2 だが 2 < 0）
+        let mut x: Vec<u32> = (0..64).collect();
+        let _ = sort_by(&mut x, &|a: &u32, b: &u32| match (b % 3 + 3 - a % 3) % 3 {
+            0 => Ordering::Equal,
+            1 => Ordering::Less,
+            _ => Ordering::Greater,
+        });
+    }
+
+    #[cfg(all(feature = "debug-validate", debug_assertions))]
+    #[test]
+    fn debug_validate_accepts_consistent_comparator() {
+        let mut x = new_u32_vec(1024);
+        assert_eq!(sort_by(&mut x, &|a, b| b.cmp(a)), Ok(()));
+        assert!(is_sorted_descending(&x));
+    }
+
+    #[test]
+    fn sort_many_sorts_each_array() {
+        let mut batch: Vec<Vec<u32>> = (0..100)
+            .map(|i| {
+                new_u32_vec(1 << (i % 8))
+                    .into_iter()
+                    .map(|v| v ^ i)
+                    .collect()
+            })
+            .collect();
+        let expected: Vec<Vec<u32>> = batch
+            .iter()
+            .map(|array| {
+                let mut array = array.clone();
+                array.sort_unstable_by(|a, b| b.cmp(a));
+                array
+            })
+            .collect();
+        batch.push(vec![3, 1, 2]);
+
+        let mut arrays: Vec<&mut [u32]> = batch.iter_mut().map(|a| &mut a[..]).collect();
+        let results = sort_many(&mut arrays, &Descending);
+        assert_eq!(results.len(), 101);
+        assert!(results[..100].iter().all(|r| r.is_ok()));
+        assert_eq!(results[100], Err(SortError::not_power_of_two(3)));
+        assert_eq!(batch[..100], expected[..]);
+        assert_eq!(batch[100], vec![3, 1, 2]);
+    }
+
+    #[test]
+    fn sort_dedup_by_key_keeps_first_per_key() {
         let mut x = vec![
-            "Rust",
-            "is",
-            "fast",
-            "and",
-            "memory-efficient",
-            "with",
-            "no",
-            "GC",
+            ("pear", 3),
+            ("apple", 1),
+            ("fig", 3),
+            ("kiwi", 2),
+            ("plum", 1),
+            ("lime", 2),
+            ("date", 5),
         ];
-        assert_eq!(sort(&mut x, &Descending), Ok(()));
+        sort_dedup_by_key(&mut x, &|&(_, k)| k);
+        assert_eq!(x, vec![("apple", 1), ("kiwi", 2), ("pear", 3), ("date", 5)]);
+
+        let mut x = new_u32_vec(5000);
+        sort_dedup_by_key(&mut x, &|v| v % 100);
+        assert_eq!(x.len(), 100);
+        assert!((0..100).all(|k| x[k as usize] % 100 == k));
+
+        let mut empty: Vec<u32> = Vec::new();
+        sort_dedup_by_key(&mut empty, &|v| *v);
+        assert!(empty.is_empty());
+    }
+
+    #[test]
+    fn three_way_partition_all_equal() {
+        let mut x = vec![4, 4, 4, 4];
+        assert_eq!(three_way_partition_by(&mut x, &|v| v.cmp(&4)), (0, 4));
+        assert_eq!(x, vec![4, 4, 4, 4]);
+    }
+
+    #[test]
+    fn three_way_partition_all_less() {
+        let mut x = vec![3, 1, 2];
+        assert_eq!(three_way_partition_by(&mut x, &|v| v.cmp(&10)), (3, 3));
+        assert_eq!(x, vec![3, 1, 2]);
+        assert_eq!(three_way_partition_by(&mut x, &|v| v.cmp(&0)), (0, 0));
+        let mut empty: Vec<u32> = Vec::new();
+        assert_eq!(three_way_partition_by(&mut empty, &|v| v.cmp(&0)), (0, 0));
+    }
+
+    #[test]
+    fn three_way_partition_mixed() {
+        let mut x = vec![5, 2, 7, 5, 8, 1, 5, 9, 3];
+        assert_eq!(three_way_partition_by(&mut x, &|v| v.cmp(&5)), (3, 6));
+        // グループ内の順序は保たれる
+        assert_eq!(x, vec![2, 1, 3, 5, 5, 5, 7, 8, 9]);
+
+        let original = new_u32_vec(100_000);
+        let mut x = original.clone();
+        let pivot = original[0];
+        let (less, greater) = three_way_partition_by(&mut x, &|v| v.cmp(&pivot));
+        assert!(x[..less].iter().all(|&v| v < pivot));
+        assert!(x[less..greater].iter().all(|&v| v == pivot));
+        assert!(x[greater..].iter().all(|&v| v > pivot));
+        assert!(is_permutation_of(&x, &original));
+    }
+
+    #[test]
+    fn sort_by_stage_timings_per_stage() {
+        let input = new_u32_vec(1 << 14);
+        let mut x = input.clone();
+        let (result, timings) = sort_by_stage_timings(&mut x, &|a, b| a.cmp(b));
+        assert_eq!(result, Ok(()));
+        assert_eq!(timings.len(), 14);
+        assert!(is_sorted_ascending(&x));
+        assert!(is_permutation_of(&x, &input));
+
+        let mut x = vec![1u32];
         assert_eq!(
-            x,
-            vec![
-                "with",
-                "no",
-                "memory-efficient",
-                "is",
-                "fast",
-                "and",
-                "Rust",
-                "GC"
-            ]
+            sort_by_stage_timings(&mut x, &|a, b| a.cmp(b)),
+            (Ok(()), vec![])
+        );
+        let mut x = vec![3, 2, 1];
+        assert_eq!(
+            sort_by_stage_timings(&mut x, &|a, b| a.cmp(b)),
+            (Err(SortError::not_power_of_two(3)), vec![])
         );
     }
 
     #[test]
-    // 年齢で昇順にソートする
-    fn sort_students_by_age_ascending() {
-        // 4人分のテストデータを作成
-        let taro = Student::new("Taro", "Yamada", 16);
-        let hanako = Student::new("Hanako", "Yamada", 14);
-        let kyoko = Student::new("Kyoko", "Ito", 15);
-        let ryosuke = Student::new("Ryosuke", "Hayashi", 17);
+    fn sort_map_by_key_orders_pairs() {
+        let map: HashMap<String, usize> = ["pear", "apple", "fig", "kiwi", "banana"]
+            .iter()
+            .map(|s| (s.to_string(), s.len()))
+            .collect();
+        let sorted = sort_map_by_key(map.clone(), &Ascending);
+        let keys: Vec<&str> = sorted.iter().map(|(k, _)| k.as_str()).collect();
+        assert_eq!(keys, vec!["apple", "banana", "fig", "kiwi", "pear"]);
+        assert!(sorted.iter().all(|(k, v)| map[k] == *v));
 
-        // ソート対象のベクタを作成する
-        let mut x = vec![&taro, &hanako, &kyoko, &ryosuke];
+        let sorted = sort_map_by_key(map.clone(), &Descending);
+        let keys: Vec<&str> = sorted.iter().map(|(k, _)| k.as_str()).collect();
+        assert_eq!(keys, vec!["pear", "kiwi", "fig", "banana", "apple"]);
 
-        // ソート後の期待値を作成する
-        let expected = vec![&hanako, &kyoko, &taro, &ryosuke];
+        let map: HashMap<u32, u32> = new_u32_vec(3000).into_iter().map(|v| (v, !v)).collect();
+        let sorted = sort_map_by_key(map.clone(), &Ascending);
+        assert_eq!(sorted.len(), map.len());
+        assert!(sorted.windows(2).all(|w| w[0].0 < w[1].0));
+        assert!(sorted.iter().all(|&(k, v)| map[&k] == v));
+
+        assert!(sort_map_by_key(HashMap::<u32, u32>::new(), &Ascending).is_empty());
+    }
+
+    // 要素を固定の大きさのページに分けて格納する、テスト用のコンテナ
+    struct PagedArray {
+        pages: Vec<Vec<u32>>,
+    }
+
+    const PAGE_LEN: usize = 100;
+
+    impl PagedArray {
+        fn new(values: &[u32]) -> Self {
+            Self {
+                pages: values.chunks(PAGE_LEN).map(|page| page.to_vec()).collect(),
+            }
+        }
+
+        fn to_vec(&self) -> Vec<u32> {
+            self.pages.concat()
+        }
+    }
+
+    impl std::ops::Index<usize> for PagedArray {
+        type Output = u32;
+
+        fn index(&self, i: usize) -> &u32 {
+            &self.pages[i / PAGE_LEN][i % PAGE_LEN]
+        }
+    }
+
+    impl std::ops::IndexMut<usize> for PagedArray {
+        fn index_mut(&mut self, i: usize) -> &mut u32 {
+            &mut self.pages[i / PAGE_LEN][i % PAGE_LEN]
+        }
+    }
+
+    #[test]
+    fn sort_indexed_paged_array() {
+        let input = new_u32_vec(1000);
+        let mut paged = PagedArray::new(&input);
+        assert_eq!(sort_indexed(&mut paged, 512, &|a, b| a.cmp(b)), Ok(()));
+        let x = paged.to_vec();
+        let mut expected = input[..512].to_vec();
+        assert_eq!(sort(&mut expected, &Ascending), Ok(()));
+        assert_eq!(x[..512], expected[..]);
+        // len以降の要素は変わらない
+        assert_eq!(x[512..], input[512..]);
 
         assert_eq!(
-            // sort_by関数でソートする。第2引数はソート順を決めるクロージャ
-            // 引数に2つのStudent構造体をとり、ageフィールドの値をcmpメソッドで
-            // 比較することで大小を決定する
-            sort_by(&mut x, &|a, b| a.age.cmp(&b.age)),
+            sort_indexed(&mut paged, 1000, &|a, b| b.cmp(a)),
+            Err(SortError::not_power_of_two(1000))
+        );
+
+        let mut strings = vec![
+            "c".to_string(),
+            "a".to_string(),
+            "d".to_string(),
+            "b".to_string(),
+        ];
+        assert_eq!(sort_indexed(&mut strings, 4, &|a, b| b.cmp(a)), Ok(()));
+        assert_eq!(strings, vec!["d", "c", "b", "a"]);
+    }
+
+    #[test]
+    fn sort_outcome_variants() {
+        let mut empty: Vec<u32> = Vec::new();
+        assert_eq!(sort_outcome(&mut empty, &Ascending), Ok(SortOutcome::Empty));
+
+        let mut x = vec![1, 2, 3, 4];
+        assert_eq!(
+            sort_outcome(&mut x, &Ascending),
+            Ok(SortOutcome::AlreadySorted)
+        );
+        assert_eq!(
+            sort_outcome(&mut x[..1], &Descending),
+            Ok(SortOutcome::AlreadySorted)
+        );
+        assert_eq!(sort_outcome(&mut x, &Descending), Ok(SortOutcome::Sorted));
+        assert_eq!(x, vec![4, 3, 2, 1]);
+
+        let mut x = new_u32_vec(1024);
+        assert_eq!(sort_outcome(&mut x, &Ascending), Ok(SortOutcome::Sorted));
+        assert!(is_sorted_ascending(&x));
+        assert_eq!(
+            sort_outcome(&mut x, &Ascending),
+            Ok(SortOutcome::AlreadySorted)
+        );
+
+        assert_eq!(
+            sort_outcome(&mut x[..3], &Ascending),
+            Err(SortError::not_power_of_two(3))
+        );
+    }
+
+    #[test]
+    fn do_sort_with_direction_fn_sorts() {
+        let input = new_u32_vec(1 << 13);
+        let mut expected = input.clone();
+        assert_eq!(sort_by(&mut expected, &|a, b| a.cmp(b)), Ok(()));
+
+        // すべての深さで標準の向きを選べば、sort_byと同じになる
+        let mut x = input.clone();
+        assert_eq!(
+            do_sort_with_direction_fn(&mut x, &|_| true, &|a, b| a.cmp(b)),
             Ok(())
         );
+        assert_eq!(x, expected);
 
-        // 結果を検証する
+        // 深さごとに向きを変えても、ソートの結果は変わらない
+        for direction in &[
+            |_| false,
+            |level: usize| level.is_multiple_of(2),
+            |level: usize| level < 3,
+        ] {
+            let mut x = input.clone();
+            assert_eq!(
+                do_sort_with_direction_fn(&mut x, direction, &|a, b| a.cmp(b)),
+                Ok(())
+            );
+            assert_eq!(x, expected);
+        }
+
+        let mut x = vec![3, 2, 1];
+        assert_eq!(
+            do_sort_with_direction_fn(&mut x, &|_| true, &|a, b| a.cmp(b)),
+            Err(SortError::not_power_of_two(3))
+        );
+    }
+
+    #[test]
+    fn resort_after_changes_repairs_order() {
+        let mut x = new_u32_vec(1 << 12);
+        assert_eq!(sort(&mut x, &Ascending), Ok(()));
+        let dirty = [0, 17, 17, 1000, 4095];
+        x[0] = u32::MAX;
+        x[17] = 0;
+        x[1000] = x[3000];
+        x[4095] = 12345;
+        let mut expected = x.clone();
+        expected.sort_unstable();
+        resort_after_changes(&mut x, &dirty);
+        assert_eq!(x, expected);
+
+        // 書き換えがなければ何もしない
+        resort_after_changes(&mut x, &[]);
         assert_eq!(x, expected);
+
+        let mut x = vec![5, 1, 3, 7, 9];
+        resort_after_changes(&mut x, &[0]);
+        assert_eq!(x, vec![1, 3, 5, 7, 9]);
+        let mut x = vec![9, 3, 1];
+        resort_after_changes(&mut x, &[0, 1, 2]);
+        assert_eq!(x, vec![1, 3, 9]);
     }
 
     #[test]
-    fn sort_students_by_name_ascending() {
-        let taro = Student::new("Taro", "Yamada", 16);
-        let hanako = Student::new("Hanako", "Yamada", 14);
-        let kyoko = Student::new("Kyoko", "Ito", 15);
-        let ryosuke = Student::new("Ryosuke", "Hayashi", 17);
+    #[should_panic(expected = "dirty index out of range")]
+    fn resort_after_changes_out_of_range() {
+        let mut x = vec![1, 2, 3];
+        resort_after_changes(&mut x, &[3]);
+    }
 
-        let mut x = vec![&taro, &hanako, &kyoko, &ryosuke];
-        let expected = vec![&ryosuke, &kyoko, &hanako, &taro];
+    #[test]
+    fn search_by_uses_sort_comparator() {
+        let mut x = vec![30u32, 10, 50, 20, 70, 40, 60, 0];
+        let desc = |a: &u32, b: &u32| b.cmp(a);
+        assert_eq!(sort_by(&mut x, &desc), Ok(()));
+        assert_eq!(x, vec![70, 60, 50, 40, 30, 20, 10, 0]);
+        assert_eq!(search_by(&x, &70, &desc), Ok(0));
+        assert_eq!(search_by(&x, &30, &desc), Ok(4));
+        assert_eq!(search_by(&x, &0, &desc), Ok(7));
+        // 見つからなければ、降順を保って挿入できる位置
+        assert_eq!(search_by(&x, &80, &desc), Err(0));
+        assert_eq!(search_by(&x, &35, &desc), Err(4));
+        let mut inserted = x.clone();
+        inserted.insert(4, 35);
+        assert!(is_sorted_descending(&inserted));
+
+        let students = vec![
+            Student::new("Ryosuke", "Hayashi", 17),
+            Student::new("Kyoko", "Ito", 15),
+            Student::new("Taro", "Yamada", 16),
+        ];
+        let by_last_name = |a: &Student, b: &Student| a.last_name.cmp(&b.last_name);
+        let target = Student::new("", "Ito", 0);
+        assert_eq!(search_by(&students, &target, &by_last_name), Ok(1));
+        let target = Student::new("", "Zenda", 0);
+        assert_eq!(search_by(&students, &target, &by_last_name), Err(3));
+        assert_eq!(search_by(&[], &1u32, &desc), Err(0));
+    }
+
+    #[test]
+    fn insert_sorted_batch_merges() {
+        let mut sorted = vec![1, 3, 5, 7, 9];
+        insert_sorted_batch(&mut sorted, vec![8, 0, 5, 10], &Ascending);
+        assert_eq!(sorted, vec![0, 1, 3, 5, 5, 7, 8, 9, 10]);
+
+        let mut sorted = vec![9, 5, 1];
+        insert_sorted_batch(&mut sorted, vec![2, 6, 10], &Descending);
+        assert_eq!(sorted, vec![10, 9, 6, 5, 2, 1]);
 
+        let input = new_u32_vec(5000);
+        let mut sorted = sorted_from(input[..3000].to_vec(), &Ascending);
+        insert_sorted_batch(&mut sorted, input[3000..].to_vec(), &Ascending);
+        assert!(is_sorted_ascending(&sorted));
+        assert!(is_permutation_of(&sorted, &input));
+
+        let mut empty = Vec::new();
+        insert_sorted_batch(&mut empty, vec![2, 1], &Ascending);
+        assert_eq!(empty, vec![1, 2]);
+        insert_sorted_batch(&mut empty, Vec::new(), &Ascending);
+        assert_eq!(empty, vec![1, 2]);
+    }
+
+    #[test]
+    fn sort_or_panic_sorts() {
+        let mut x = vec![10, 30, 11, 20];
+        sort_or_panic(&mut x, &Descending);
+        assert_eq!(x, vec![30, 20, 11, 10]);
+    }
+
+    #[test]
+    #[should_panic(
+        expected = "The length of x is not a power of two. (x.len(): 5) Pad it to 8 elements to sort it."
+    )]
+    fn sort_or_panic_non_power_of_two() {
+        let mut x = vec![10, 30, 11, 20, 4];
+        sort_or_panic(&mut x, &Ascending);
+    }
+
+    #[test]
+    fn sort_by_with_stage_stats_reports_cumulative_counts() {
+        let input = new_u32_vec(1 << 13);
+        let mut x = input.clone();
+        let stats = std::sync::Mutex::new(Vec::new());
         assert_eq!(
-            sort_by(
-                &mut x,
-                // まずlast_nameを比較する
-                &|a, b| a
-                    .last_name
-                    .cmp(&b.last_name)
-                    // もしlast_nameが等しくない（LessまたはGreater）ならそれを返す
-                    // last_nameが等しい（Equal）ならfirst_nameを比較する
-                    .then_with(|| a.first_name.cmp(&b.first_name))
-            ),
+            sort_by_with_stage_stats(&mut x, &|a, b| a.cmp(b), |s| stats.lock().unwrap().push(s)),
+            Ok(())
+        );
+        assert!(is_sorted_ascending(&x));
+
+        let stats = stats.into_inner().unwrap();
+        assert_eq!(stats.len(), 13);
+        assert!(stats.iter().enumerate().all(|(i, s)| s.stage == i + 1));
+        assert!(stats
+            .windows(2)
+            .all(|w| w[0].comparisons < w[1].comparisons && w[0].swaps <= w[1].swaps));
+        // 最後の累計はsort_by_iterativeで数えた回数と同じ
+        let mut y = input;
+        let metrics = sort_by_iterative(&mut y, &|a, b| a.cmp(b)).unwrap();
+        let last = stats.last().unwrap();
+        assert_eq!(
+            (last.comparisons, last.swaps),
+            (metrics.comparisons, metrics.swaps)
+        );
+
+        assert!(sort_by_with_stage_stats(&mut x[..3], &|a, b| a.cmp(b), |_| ()).is_err());
+    }
+
+    #[test]
+    fn sort_by_distance_to_target() {
+        let mut x: Vec<i32> = vec![10, -3, 7, 4, 6, 5, 0, 12];
+        let target = 5;
+        assert_eq!(
+            sort_by_distance(&mut x, &|v: &i32| (v - target).abs()),
+            Ok(())
+        );
+        // 距離が等しい4と6、10と0は元の順序のまま
+        assert_eq!(x, vec![5, 4, 6, 7, 10, 0, 12, -3]);
+
+        // 原点からの距離（の2乗）が近い順に点を並べる
+        let mut points = vec![(3, 4), (-1, 0), (0, -2), (1, 1)];
+        assert_eq!(
+            sort_by_distance(&mut points, &|&(x, y): &(i32, i32)| x * x + y * y),
             Ok(())
         );
+        assert_eq!(points, vec![(-1, 0), (1, 1), (0, -2), (3, 4)]);
+
+        assert!(sort_by_distance(&mut x[..3], &|v: &i32| v.abs()).is_err());
+    }
+
+    #[test]
+    fn sort_by_local_accepts_non_sync_comparator() {
+        use std::cell::Cell;
+        use std::rc::Rc;
+
+        // Rc<Cell<_>>はSyncではないので、このクロージャはsort_byには渡せない
+        let calls = Rc::new(Cell::new(0u64));
+        let counter = Rc::clone(&calls);
+        let comparator = move |a: &u32, b: &u32| {
+            counter.set(counter.get() + 1);
+            a.cmp(b)
+        };
+        let input = new_u32_vec(1024);
+        let mut x = input.clone();
+        assert_eq!(sort_by_local(&mut x, &comparator), Ok(()));
+        let mut expected = input;
+        assert_eq!(sort_by(&mut expected, &|a, b| a.cmp(b)), Ok(()));
+        assert_eq!(x, expected);
+        // 比較の回数はネットワークの比較の回数と同じ
+        assert_eq!(calls.get(), 1024 * 10 * 11 / 4);
+
+        assert!(sort_by_local(&mut x[..3], &comparator).is_err());
+    }
+
+    #[test]
+    fn sort_by_benchmarked_reports_throughput() {
+        let mut x = new_u32_vec(1 << 16);
+        let report = sort_by_benchmarked(&mut x, &|a, b| a.cmp(b)).unwrap();
+        assert!(is_sorted_ascending(&x));
+        assert_eq!(report.n, 1 << 16);
+        assert!(report.elapsed > Duration::ZERO);
+        assert!(report.elements_per_sec > 0.0);
+        let expected = report.n as f64 / report.elapsed.as_secs_f64();
+        assert!((report.elements_per_sec - expected).abs() <= expected * 1e-12);
+
+        assert!(sort_by_benchmarked(&mut x[..3], &|a, b| a.cmp(b)).is_err());
+    }
+
+    #[cfg(feature = "tokio")]
+    #[test]
+    fn sort_by_async_on_blocking_pool() {
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .build()
+            .unwrap();
+        let input = new_u32_vec(1 << 14);
+        let sorted = runtime
+            .block_on(sort_by_async(input.clone(), |a: &u32, b: &u32| b.cmp(a)))
+            .unwrap();
+        assert!(is_sorted_descending(&sorted));
+        assert!(is_permutation_of(&sorted, &input));
+
+        let result = runtime.block_on(sort_by_async(vec![3, 1, 2], |a: &u32, b: &u32| a.cmp(b)));
+        assert_eq!(result, Err(SortError::not_power_of_two(3)));
+    }
+
+    #[test]
+    fn sort_vec_default_padded_default_first() {
+        // u32のdefault（0）はどの値よりも小さいので、昇順では先頭に集まる
+        let mut x = vec![10u32, 30, 11, 20, 4];
+        sort_vec_default_padded(&mut x, &Ascending);
+        assert_eq!(x, vec![4, 10, 11, 20, 30]);
+        // 降順では末尾に集まる
+        sort_vec_default_padded(&mut x, &Descending);
+        assert_eq!(x, vec![30, 20, 11, 10, 4]);
+        // データに含まれる0は残る
+        let mut x = vec![0u32, 5, 0];
+        sort_vec_default_padded(&mut x, &Ascending);
+        assert_eq!(x, vec![0, 0, 5]);
+    }
+
+    #[test]
+    fn sort_vec_default_padded_interleaved() {
+        // i32のdefault（0）は負の数と正の数の間に並ぶ
+        let mut x = vec![3, -1, 0, 7, -5, 2];
+        sort_vec_default_padded(&mut x, &Ascending);
+        assert_eq!(x, vec![-5, -1, 0, 2, 3, 7]);
+        let mut x = vec![3, -1, 7, -5, 2];
+        sort_vec_default_padded(&mut x, &Descending);
+        assert_eq!(x, vec![7, 3, 2, -1, -5]);
+
+        let mut x: Vec<String> = vec!["b".into(), "".into(), "a".into()];
+        sort_vec_default_padded(&mut x, &Ascending);
+        assert_eq!(x, vec!["", "a", "b"]);
+
+        let mut empty: Vec<i32> = Vec::new();
+        sort_vec_default_padded(&mut empty, &Ascending);
+        assert!(empty.is_empty());
+
+        let input: Vec<i64> = new_u32_vec(3000)
+            .into_iter()
+            .map(|v| v as i64 - (1 << 31))
+            .collect();
+        let mut x = input.clone();
+        sort_vec_default_padded(&mut x, &Ascending);
+        let mut expected = input;
+        expected.sort();
         assert_eq!(x, expected);
     }
+
+    #[test]
+    fn reverse_in_place_flips_direction() {
+        for &bits in &[0, 1, 5, 12] {
+            let input = new_u32_vec(1 << bits);
+            let mut x = input.clone();
+            sort(&mut x, &Ascending).unwrap();
+            reverse_in_place(&mut x);
+            let mut y = input;
+            sort(&mut y, &Descending).unwrap();
+            assert_eq!(x, y);
+            reverse_in_place(&mut x);
+            assert!(is_sorted_ascending(&x));
+        }
+    }
+
+    #[test]
+    fn compare_backends_sorts_both_ways() {
+        for &bits in &[0, 4, 12, 16] {
+            let (input, expected) = new_test_case(1 << bits, bits);
+            let mut x = input.clone();
+            let (sequential, parallel) = compare_backends(&mut x, &Ascending).unwrap();
+            assert_eq!(x, expected);
+            // 計測した時間の大小は環境によるので、確かめない
+            let _ = (sequential, parallel);
+
+            let mut x = input;
+            compare_backends(&mut x, &Descending).unwrap();
+            assert!(x.iter().eq(expected.iter().rev()));
+        }
+
+        let mut x = vec![3, 1, 2];
+        assert_eq!(
+            compare_backends(&mut x, &Ascending),
+            Err(SortError::not_power_of_two(3))
+        );
+        assert_eq!(x, vec![3, 1, 2]);
+    }
+
+    #[test]
+    fn sort_and_runs_by_equal_clusters() {
+        let mut x = vec![5, 3, 5, 1, 3, 5, 9, 3];
+        let runs = sort_and_runs_by(&mut x, &|a, b| a.cmp(b)).unwrap();
+        assert_eq!(x, vec![1, 3, 3, 3, 5, 5, 5, 9]);
+        assert_eq!(runs, vec![0..1, 1..4, 4..7, 7..8]);
+
+        // 大文字と小文字を区別しない比較では、"b"と"B"が同じランに入る
+        let mut x = vec!["b", "a", "C", "B", "c", "A", "c", "d"];
+        let runs =
+            sort_and_runs_by(&mut x, &|a, b| a.to_lowercase().cmp(&b.to_lowercase())).unwrap();
+        assert_eq!(runs, vec![0..2, 2..4, 4..7, 7..8]);
+        for run in &runs {
+            assert!(x[run.clone()]
+                .iter()
+                .all(|s| s.eq_ignore_ascii_case(x[run.start])));
+        }
+
+        // すべて異なる要素なら、ランはすべて長さ1になる
+        let mut x: Vec<u32> = (0..16).rev().collect();
+        let runs = sort_and_runs_by(&mut x, &|a, b| a.cmp(b)).unwrap();
+        assert_eq!(runs, (0..16).map(|i| i..i + 1).collect::<Vec<_>>());
+
+        let mut x = vec![1, 1, 1];
+        assert_eq!(
+            sort_and_runs_by(&mut x, &|a, b| a.cmp(b)),
+            Err(SortError::not_power_of_two(3))
+        );
+    }
 }
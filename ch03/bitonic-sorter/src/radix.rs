@@ -0,0 +1,357 @@
+use super::SortOrder;
+use rayon::prelude::*;
+
+// 1回のパスで扱う桁のビット数と、桁がとりうる値の数（バケット数）
+const RADIX_BITS: u32 = 8;
+const BUCKETS: usize = 1 << RADIX_BITS;
+
+// ヒストグラムを作るときと要素を分配するときの、1タスクあたりの要素数
+const CHUNK_LEN: usize = 1 << 16;
+
+// 整数のLSD基数ソート（下位の桁から順に安定な分配を繰り返す）
+// 比較を使わないので、大きな整数の配列ではバイトニックソートより速い
+// 要素数は2のべき乗でなくてもよい
+pub fn sort_u32(array: &mut [u32], order: &SortOrder) {
+    lsd_sort(array, 32, order, |value, shift| (value >> shift) as usize);
+}
+
+pub fn sort_u64(array: &mut [u64], order: &SortOrder) {
+    lsd_sort(array, 64, order, |value, shift| (value >> shift) as usize);
+}
+
+// u8の配列を計数ソートでソートする
+// 値は256通りしかないので、値ごとの個数（ヒストグラム）を並列に数えてから、
+// 小さい値（降順なら大きい値）から順に、その個数だけ書き戻せばよい
+// 計算量はO(n)で、ヒストグラムのほかに追加のメモリを使わない
+// 要素数は2のべき乗でなくてもよい
+pub fn sort_u8(array: &mut [u8], order: &SortOrder) {
+    let counts = histogram(array, &|value| value as usize);
+    let mut start = 0;
+    let mut fill = |value: usize| {
+        let end = start + counts[value];
+        array[start..end].fill(value as u8);
+        start = end;
+    };
+    match *order {
+        SortOrder::Ascending => (0..BUCKETS).for_each(&mut fill),
+        SortOrder::Descending => (0..BUCKETS).rev().for_each(&mut fill),
+    }
+}
+
+// i32の配列を降順にソートする
+// i32のビット列をu32として見るとき、符号ビットを反転すると大小関係が保たれ
+// （i32::MIN → 0、-1 → 0x7FFF_FFFF、0 → 0x8000_0000、i32::MAX → 0xFFFF_FFFF）、
+// さらに全ビットを反転（u32::MAX - u）すると大小関係が逆になる
+// 2つを合わせたx ^ 0x7FFF_FFFFは、i32の降順をu32の昇順に移す1対1の変換になるので、
+// この値を桁として昇順に分配すれば降順にソートできる（比較関数を逆にする必要がない）
+// 変換は桁を取り出すときにだけ行い、配列の値そのものは書き換えないので、元に戻す処理も要らない
+pub fn sort_i32_descending(array: &mut [i32]) {
+    lsd_sort(array, 32, &SortOrder::Ascending, |value, shift| {
+        ((value as u32 ^ 0x7FFF_FFFF) >> shift) as usize
+    });
+}
+
+// bitsビットの整数をRADIX_BITSずつ、下位の桁から分配する
+// digitは値からshiftビット目以降を取り出す関数で、下位RADIX_BITSビットが桁になる
+// パスは前のパスの結果を使うので順に行うが、各パスの中の数え上げと分配はチャンクごとに
+// 並列に行う（scatter）
+fn lsd_sort<T, D>(array: &mut [T], bits: u32, order: &SortOrder, digit: D)
+where
+    T: Copy + Send + Sync,
+    D: Sync + Fn(T, u32) -> usize,
+{
+    if array.len() <= 1 {
+        return;
+    }
+    // 降順のときは桁の値を反転して分配する。各パスは安定なので、全体も安定になる
+    let descending = match *order {
+        SortOrder::Ascending => false,
+        SortOrder::Descending => true,
+    };
+    let bucket_of = |value: T, shift: u32| {
+        let d = digit(value, shift) & (BUCKETS - 1);
+        if descending {
+            BUCKETS - 1 - d
+        } else {
+            d
+        }
+    };
+
+    let mut buffer = array.to_vec();
+    let (mut src, mut dst) = (&mut *array, &mut buffer[..]);
+    for pass in 0..bits / RADIX_BITS {
+        let shift = pass * RADIX_BITS;
+        // すべての要素が同じバケットに入るなら、このパスでは並びが変わらない
+        if scatter(src, dst, &|value| bucket_of(value, shift)) {
+            std::mem::swap(&mut src, &mut dst);
+        }
+    }
+    // 最後に分配した結果がbufferの側にあるなら、arrayへ書き戻す
+    if src.as_ptr() != array.as_ptr() {
+        array.copy_from_slice(&buffer);
+    }
+}
+
+// srcの要素をbucket_ofで求めたバケットの順に、バケット内では元の順序のままdstへ分配する
+// srcをhistogramと同じチャンクに分け、チャンクごとに各バケットの個数を並列に数える
+// バケットbの中では前のチャンクの要素が先に並ぶので、チャンクcがバケットbに書き始める位置は
+// （bより前のバケットの要素数の合計）+（cより前のチャンクがbに入れる要素数の合計）になる
+// この位置が決まれば、各チャンクは互いに重ならない位置へ並列に書き込める
+// すべての要素が同じバケットに入るなら、並びが変わらないので何もせずにfalseを返す
+fn scatter<T, B>(src: &[T], dst: &mut [T], bucket_of: &B) -> bool
+where
+    T: Copy + Send + Sync,
+    B: Sync + Fn(T) -> usize,
+{
+    assert_eq!(src.len(), dst.len());
+    let chunk_counts: Vec<[usize; BUCKETS]> = src
+        .par_chunks(CHUNK_LEN)
+        .map(|chunk| count_buckets(chunk, bucket_of))
+        .collect();
+    if (0..BUCKETS).any(|b| chunk_counts.iter().map(|counts| counts[b]).sum::<usize>() == src.len())
+    {
+        return false;
+    }
+
+    let mut starts = vec![[0; BUCKETS]; chunk_counts.len()];
+    let mut total = 0;
+    for b in 0..BUCKETS {
+        for (start, counts) in starts.iter_mut().zip(chunk_counts.iter()) {
+            start[b] = total;
+            total += counts[b];
+        }
+    }
+
+    let out = SharedMutPtr(dst.as_mut_ptr());
+    src.par_chunks(CHUNK_LEN)
+        .zip(starts)
+        .for_each(|(chunk, mut offsets)| {
+            for &value in chunk {
+                let bucket = bucket_of(value);
+                // 安全性：offsetsはチャンクとバケットの組ごとに重ならない区間を指し、
+                // 区間の長さはその組の要素数と等しいので、書き込む位置はdstの範囲内で、
+                // 同じ位置に2つのスレッドが書き込むことはない（TはCopyなので古い値の破棄も要らない）
+                unsafe { out.get().add(offsets[bucket]).write(value) };
+                offsets[bucket] += 1;
+            }
+        });
+    true
+}
+
+// スレッド間で共有する、書き込み先の配列の先頭を指すポインタ
+// 書き込む位置が重ならないことは、使う側（scatter）が保証する
+struct SharedMutPtr<T>(*mut T);
+
+unsafe impl<T: Send> Send for SharedMutPtr<T> {}
+unsafe impl<T: Send> Sync for SharedMutPtr<T> {}
+
+impl<T> SharedMutPtr<T> {
+    fn get(&self) -> *mut T {
+        self.0
+    }
+}
+
+fn count_buckets<T, B>(chunk: &[T], bucket_of: &B) -> [usize; BUCKETS]
+where
+    T: Copy,
+    B: Fn(T) -> usize,
+{
+    let mut counts = [0; BUCKETS];
+    for &value in chunk {
+        counts[bucket_of(value)] += 1;
+    }
+    counts
+}
+
+// 各バケットに入る要素の数を数える。配列をチャンクに分けて並列に数え、最後に合計する
+fn histogram<T, B>(array: &[T], bucket_of: &B) -> [usize; BUCKETS]
+where
+    T: Copy + Sync,
+    B: Sync + Fn(T) -> usize,
+{
+    array
+        .par_chunks(CHUNK_LEN)
+        .map(|chunk| count_buckets(chunk, bucket_of))
+        .reduce(
+            || [0; BUCKETS],
+            |mut total, counts| {
+                for (t, c) in total.iter_mut().zip(counts.iter()) {
+                    *t += c;
+                }
+                total
+            },
+        )
+}
+
+// 昇順にソートし、最小値から最大値までをbuckets個の等しい幅の区間に分けたときの、
+// 各区間に入る要素の数を返す
+// ソート後は先頭が最小値、末尾が最大値になるので、範囲を求めるために別に走査する必要はない
+// i番目の区間は、min + (max - min + 1) * i / buckets 以上、min + (max - min + 1) * (i + 1) / buckets
+// 未満の値を数える。配列が空なら、すべての区間の数は0になる
+// 要素数は2のべき乗でなくてもよい。bucketsが0ならパニックする
+pub fn sort_u32_with_histogram(array: &mut [u32], buckets: usize) -> Vec<u32> {
+    assert!(buckets > 0, "the number of buckets must be positive");
+    sort_u32(array, &SortOrder::Ascending);
+    let mut histogram = vec![0; buckets];
+    if let (Some(&min), Some(&max)) = (array.first(), array.last()) {
+        // 値の範囲の幅（要素の種類の数）。u32::MAX + 1になることがあるのでu64で求める
+        let range = (max - min) as u64 + 1;
+        for &value in array.iter() {
+            let bucket = (value - min) as u64 * buckets as u64 / range;
+            histogram[bucket as usize] += 1;
+        }
+    }
+    histogram
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        scatter, sort_i32_descending, sort_u32, sort_u32_with_histogram, sort_u64, sort_u8,
+        CHUNK_LEN,
+    };
+    use crate::parallel::sort;
+    use crate::utils::new_u32_vec;
+    use crate::SortOrder::*;
+
+    // 基数ソートとバイトニックソートの結果が一致することを確かめる
+    fn assert_same_as_bitonic(input: Vec<u32>) {
+        for order in &[Ascending, Descending] {
+            let mut expected = input.clone();
+            assert_eq!(sort(&mut expected, order), Ok(()));
+            let mut x = input.clone();
+            sort_u32(&mut x, order);
+            assert_eq!(x, expected);
+        }
+    }
+
+    #[test]
+    fn radix_u32_random() {
+        assert_same_as_bitonic(new_u32_vec(8));
+        assert_same_as_bitonic(new_u32_vec(65536));
+        assert_same_as_bitonic(new_u32_vec(1 << 18));
+    }
+
+    #[test]
+    fn radix_u32_adversarial() {
+        // すべて同じ値
+        assert_same_as_bitonic(vec![7; 1024]);
+        // ソート済みと逆順
+        assert_same_as_bitonic((0..1024).collect());
+        assert_same_as_bitonic((0..1024).rev().collect());
+        // 最大値と0が交互に並ぶ
+        assert_same_as_bitonic(
+            (0..1024)
+                .map(|i| if i % 2 == 0 { u32::MAX } else { 0 })
+                .collect(),
+        );
+        // 上位のバイトだけが異なる
+        assert_same_as_bitonic((0..1024).map(|i| (i as u32 % 7) << 24).collect());
+        // 1要素
+        assert_same_as_bitonic(vec![42]);
+    }
+
+    #[test]
+    fn radix_non_power_of_two_length() {
+        let mut x = vec![10, 30, 11, 20, 4, 330, 21];
+        sort_u32(&mut x, &Ascending);
+        assert_eq!(x, vec![4, 10, 11, 20, 21, 30, 330]);
+        let mut empty: Vec<u32> = Vec::new();
+        sort_u32(&mut empty, &Ascending);
+        assert!(empty.is_empty());
+    }
+
+    #[test]
+    fn radix_u64() {
+        let mut x: Vec<u64> = new_u32_vec(4096)
+            .into_iter()
+            .map(|v| (v as u64) << 32 | (v as u64 % 1000))
+            .collect();
+        let mut expected = x.clone();
+        assert_eq!(sort(&mut expected, &Descending), Ok(()));
+        sort_u64(&mut x, &Descending);
+        assert_eq!(x, expected);
+    }
+
+    #[test]
+    fn radix_i32_descending() {
+        let mut x: Vec<i32> = new_u32_vec(65536).into_iter().map(|v| v as i32).collect();
+        x.extend_from_slice(&[i32::MIN, i32::MAX, 0, -1, 1, i32::MIN + 1, i32::MAX - 1]);
+        let mut expected = x.clone();
+        expected.sort_by(|a, b| b.cmp(a));
+        sort_i32_descending(&mut x);
+        assert_eq!(x, expected);
+
+        let mut x = vec![-3, 7, 0, -3, i32::MIN];
+        sort_i32_descending(&mut x);
+        assert_eq!(x, vec![7, 0, -3, -3, i32::MIN]);
+    }
+
+    #[test]
+    fn sort_with_histogram() {
+        // 範囲は10..=29（幅20）なので、4つの区間は10..15、15..20、20..25、25..30
+        let mut x = vec![29, 10, 14, 15, 22, 11, 24, 25, 19, 20];
+        assert_eq!(sort_u32_with_histogram(&mut x, 4), vec![3, 2, 3, 2]);
+        assert_eq!(x, vec![10, 11, 14, 15, 19, 20, 22, 24, 25, 29]);
+
+        // すべて同じ値なら、最初の区間にすべて入る
+        let mut x = vec![7; 5];
+        assert_eq!(sort_u32_with_histogram(&mut x, 3), vec![5, 0, 0]);
+
+        let mut x = vec![u32::MAX, 0, u32::MAX / 2, 1];
+        assert_eq!(sort_u32_with_histogram(&mut x, 2), vec![3, 1]);
+        assert_eq!(x, vec![0, 1, u32::MAX / 2, u32::MAX]);
+
+        let mut x = new_u32_vec(10_000);
+        let histogram = sort_u32_with_histogram(&mut x, 16);
+        assert_eq!(histogram.iter().sum::<u32>(), 10_000);
+        assert!(x.windows(2).all(|w| w[0] <= w[1]));
+
+        assert_eq!(sort_u32_with_histogram(&mut [], 2), vec![0, 0]);
+    }
+
+    #[test]
+    fn counting_sort_u8() {
+        let mut x: Vec<u8> = Vec::new();
+        sort_u8(&mut x, &Ascending);
+        assert!(x.is_empty());
+
+        let mut x = vec![42u8; 1000];
+        sort_u8(&mut x, &Descending);
+        assert_eq!(x, vec![42u8; 1000]);
+
+        // チャンクをまたぐ長さで、2のべき乗でない長さの乱数のバイト列
+        let input: Vec<u8> = new_u32_vec(200_001).iter().map(|&v| v as u8).collect();
+        let mut expected = input.clone();
+        expected.sort_unstable();
+        let mut x = input.clone();
+        sort_u8(&mut x, &Ascending);
+        assert_eq!(x, expected);
+
+        expected.reverse();
+        let mut x = input;
+        sort_u8(&mut x, &Descending);
+        assert_eq!(x, expected);
+    }
+
+    #[test]
+    fn parallel_scatter_is_stable() {
+        // 上位32ビットにバケット、下位32ビットに元の位置を入れた値を、複数のチャンクに
+        // またがる（チャンクの長さの倍数でない）長さで分配する
+        let len = CHUNK_LEN * 3 + 12345;
+        let src: Vec<u64> = new_u32_vec(len)
+            .into_iter()
+            .enumerate()
+            .map(|(i, v)| ((v % 17) as u64) << 32 | i as u64)
+            .collect();
+        let mut dst = vec![0; len];
+        assert!(scatter(&src, &mut dst, &|v: u64| (v >> 32) as usize));
+        // バケットの順に並び、同じバケットの中では元の位置の順に並ぶ
+        assert!(dst.windows(2).all(|w| w[0] < w[1]));
+
+        // すべての要素が同じバケットに入るなら何もしない
+        let mut untouched = vec![0; len];
+        assert!(!scatter(&src, &mut untouched, &|_| 3));
+        assert!(untouched.iter().all(|&v| v == 0));
+    }
+}
@@ -0,0 +1,129 @@
+use super::utils::require_power_of_two;
+use super::{SortError, SortMetrics, SortOrder};
+use std::cmp::Ordering;
+
+// 第3段階（third）と同じ順次処理のバイトニックソートに、parallelモジュールと同じ
+// SortError と SortMetrics を使えるようにしたもの
+// スレッドを使わないので要素型にSendは要らず、比較と交換の回数は普通の変数で数えられる
+// parallelと異なり、ソート済みや逆順の入力を見分ける事前の確認は行わない
+
+pub fn sort<T: Ord>(array: &mut [T], order: &SortOrder) -> Result<(), SortError> {
+    match *order {
+        SortOrder::Ascending => sort_by(array, &|a, b| a.cmp(b)),
+        SortOrder::Descending => sort_by(array, &|a, b| b.cmp(a)),
+    }
+}
+
+pub fn sort_by<T, F>(array: &mut [T], comparator: &F) -> Result<(), SortError>
+where
+    F: Fn(&T, &T) -> Ordering,
+{
+    sort_by_counting(array, comparator).map(|_| ())
+}
+
+// sort_byと同じようにソートし、比較と交換の回数を返す
+// 回数はparallel::sort_by_countingと同じ数え方をする（parallel_tasksは常に0）
+// ただしparallelはソート済みや逆順の入力でネットワークを実行せず、確認の比較だけを数えるので、
+// そのような入力では回数が一致しない
+pub fn sort_by_counting<T, F>(array: &mut [T], comparator: &F) -> Result<SortMetrics, SortError>
+where
+    F: Fn(&T, &T) -> Ordering,
+{
+    require_power_of_two(array.len())?;
+    let mut metrics = SortMetrics::default();
+    do_sort(array, true, comparator, &mut metrics);
+    Ok(metrics)
+}
+
+fn do_sort<T, F>(array: &mut [T], forward: bool, comparator: &F, metrics: &mut SortMetrics)
+where
+    F: Fn(&T, &T) -> Ordering,
+{
+    if array.len() > 1 {
+        let mid_point = array.len() / 2;
+        do_sort(&mut array[..mid_point], true, comparator, metrics);
+        do_sort(&mut array[mid_point..], false, comparator, metrics);
+        sub_sort(array, forward, comparator, metrics);
+    }
+}
+
+fn sub_sort<T, F>(array: &mut [T], forward: bool, comparator: &F, metrics: &mut SortMetrics)
+where
+    F: Fn(&T, &T) -> Ordering,
+{
+    if array.len() > 1 {
+        compare_and_swap(array, forward, comparator, metrics);
+        let mid_point = array.len() / 2;
+        sub_sort(&mut array[..mid_point], forward, comparator, metrics);
+        sub_sort(&mut array[mid_point..], forward, comparator, metrics);
+    }
+}
+
+fn compare_and_swap<T, F>(array: &mut [T], forward: bool, comparator: &F, metrics: &mut SortMetrics)
+where
+    F: Fn(&T, &T) -> Ordering,
+{
+    let swap_condition = if forward {
+        Ordering::Greater
+    } else {
+        Ordering::Less
+    };
+    let mid_point = array.len() / 2;
+    for i in 0..mid_point {
+        metrics.comparisons += 1;
+        if comparator(&array[i], &array[mid_point + i]) == swap_condition {
+            array.swap(i, mid_point + i);
+            metrics.swaps += 1;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{sort, sort_by, sort_by_counting};
+    use crate::parallel;
+    use crate::utils::{is_sorted_ascending, is_sorted_descending, new_u32_vec};
+    use crate::SortError;
+    use crate::SortOrder::*;
+    use std::cell::Cell;
+
+    #[test]
+    fn sort_u32() {
+        let mut x = new_u32_vec(1024);
+        assert_eq!(sort(&mut x, &Ascending), Ok(()));
+        assert!(is_sorted_ascending(&x));
+        assert_eq!(sort(&mut x, &Descending), Ok(()));
+        assert!(is_sorted_descending(&x));
+        assert_eq!(
+            sort(&mut x[..3], &Ascending),
+            Err(SortError::not_power_of_two(3))
+        );
+    }
+
+    #[test]
+    fn sort_non_send_elements() {
+        // Cellを含む値（Syncではない）や、Rcのようにスレッド間で送れない値もソートできる
+        let mut x: Vec<std::rc::Rc<Cell<u32>>> = [3, 1, 4, 2]
+            .iter()
+            .map(|&v| std::rc::Rc::new(Cell::new(v)))
+            .collect();
+        assert_eq!(sort_by(&mut x, &|a, b| a.get().cmp(&b.get())), Ok(()));
+        let values: Vec<u32> = x.iter().map(|v| v.get()).collect();
+        assert_eq!(values, vec![1, 2, 3, 4]);
+    }
+
+    #[test]
+    fn counts_agree_with_parallel() {
+        for &bits in &[1, 3, 10, 14] {
+            let input = new_u32_vec(1 << bits);
+            let mut x = input.clone();
+            let seq_metrics = sort_by_counting(&mut x, &|a, b| a.cmp(b)).unwrap();
+            let mut y = input;
+            let par_metrics = parallel::sort_by_counting(&mut y, &|a, b| a.cmp(b)).unwrap();
+            assert_eq!(x, y);
+            assert_eq!(seq_metrics.comparisons, par_metrics.comparisons);
+            assert_eq!(seq_metrics.swaps, par_metrics.swaps);
+            assert_eq!(seq_metrics.parallel_tasks, 0);
+        }
+    }
+}
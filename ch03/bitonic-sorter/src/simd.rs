@@ -0,0 +1,330 @@
+use super::parallel::{apply_permutation, parallel_threshold};
+use super::radix::sort_u64;
+use super::{SortError, SortOrder};
+use rayon::prelude::*;
+
+// キーを取り出すときの、1タスクあたりの要素数
+const CHUNK_LEN: usize = 1 << 14;
+
+// 構造体をu32のフィールド（IDなど）の順にソートする高速な経路
+// 1. keyで各要素のキーを並列に取り出す
+// 2. キーと元の位置を(キー << 32) | 位置 のu64にまとめる。x86_64でAVX2が使えるときは
+//    SIMD命令で4要素ずつまとめる
+// 3. u64の配列を基数ソートし、下位32ビットの位置から並べ替えの順序を得て要素を並べ替える
+// 位置が下位のビットに入るので、キーが等しい要素は元の順序が保たれる（安定ソート）
+// キーはクロージャで取り出すので、要素ごとにフィールドの位置が変わる型（enumなど）にも使える
+// （SIMDのgather命令はフィールドの位置が一定でないと使えないので、取り出しには使っていない）
+// 要素数は2のべき乗でなくてもよい。位置がu32に収まらないほど大きな配列は、
+// 比較によるparallel::sort_keysと同じ結果になるように一度に並べ替える
+pub fn sort_by_u32_key_simd<T, F>(array: &mut [T], key: &F)
+where
+    T: Send + Sync,
+    F: Sync + Fn(&T) -> u32,
+{
+    if array.len() > u32::MAX as usize {
+        let mut keyed: Vec<(u32, usize)> = array
+            .par_iter()
+            .enumerate()
+            .map(|(i, value)| (key(value), i))
+            .collect();
+        keyed.par_sort_unstable();
+        let mut permutation: Vec<usize> = keyed.into_iter().map(|(_, i)| i).collect();
+        apply_permutation(array, &mut permutation);
+        return;
+    }
+
+    let mut keys = vec![0u32; array.len()];
+    keys.par_chunks_mut(CHUNK_LEN)
+        .zip(array.par_chunks(CHUNK_LEN))
+        .for_each(|(keys, values)| {
+            for (k, value) in keys.iter_mut().zip(values) {
+                *k = key(value);
+            }
+        });
+
+    let mut packed = vec![0u64; keys.len()];
+    packed
+        .par_chunks_mut(CHUNK_LEN)
+        .zip(keys.par_chunks(CHUNK_LEN))
+        .enumerate()
+        .for_each(|(chunk, (packed, keys))| pack_keys(packed, keys, chunk * CHUNK_LEN));
+
+    sort_u64(&mut packed, &SortOrder::Ascending);
+    let mut permutation: Vec<usize> = packed
+        .par_iter()
+        .map(|&p| (p & u32::MAX as u64) as usize)
+        .collect();
+    apply_permutation(array, &mut permutation);
+}
+
+// packed[i] = (keys[i] << 32) | (start + i) を計算する
+fn pack_keys(packed: &mut [u64], keys: &[u32], start: usize) {
+    #[cfg(target_arch = "x86_64")]
+    {
+        if is_x86_feature_detected!("avx2") {
+            // 安全性：AVX2が使えることを実行時に確認した
+            unsafe { pack_keys_avx2(packed, keys, start) };
+            return;
+        }
+    }
+    pack_keys_scalar(packed, keys, start);
+}
+
+fn pack_keys_scalar(packed: &mut [u64], keys: &[u32], start: usize) {
+    for (i, (p, &k)) in packed.iter_mut().zip(keys).enumerate() {
+        *p = (k as u64) << 32 | (start + i) as u64;
+    }
+}
+
+/// # Safety
+///
+/// AVX2に対応したCPUでのみ呼び出せる。packedとkeysは同じ長さでなければならない
+#[cfg(target_arch = "x86_64")]
+#[target_feature(enable = "avx2")]
+unsafe fn pack_keys_avx2(packed: &mut [u64], keys: &[u32], start: usize) {
+    use std::arch::x86_64::*;
+
+    debug_assert_eq!(packed.len(), keys.len());
+    let lanes = keys.len() / 4 * 4;
+    let step = _mm256_set1_epi64x(4);
+    let mut positions = _mm256_set_epi64x(
+        start as i64 + 3,
+        start as i64 + 2,
+        start as i64 + 1,
+        start as i64,
+    );
+    for i in (0..lanes).step_by(4) {
+        // 4つのu32のキーをu64に広げて32ビット左にずらし、位置と論理和をとる
+        let k = _mm_loadu_si128(keys.as_ptr().add(i) as *const __m128i);
+        let k = _mm256_slli_epi64(_mm256_cvtepu32_epi64(k), 32);
+        let p = _mm256_or_si256(k, positions);
+        _mm256_storeu_si256(packed.as_mut_ptr().add(i) as *mut __m256i, p);
+        positions = _mm256_add_epi64(positions, step);
+    }
+    pack_keys_scalar(&mut packed[lanes..], &keys[lanes..], start + lanes);
+}
+
+// u32の配列をバイトニックソートする
+// x86_64でAVX2が使えるときは、比較と交換を8要素ずつSIMD命令（最小値と最大値）で行う
+// AVX2が使えなければ、同じ手順を1要素ずつ行う
+// 結果はparallel::sortと同じになる
+pub fn sort_u32(array: &mut [u32], order: &SortOrder) -> Result<(), SortError> {
+    if !array.len().is_power_of_two() {
+        return Err(SortError::not_power_of_two(array.len()));
+    }
+    let forward = match *order {
+        SortOrder::Ascending => true,
+        SortOrder::Descending => false,
+    };
+    if avx2_available() {
+        sort_u32_avx2(array, forward);
+    } else {
+        sort_u32_scalar(array, forward);
+    }
+    Ok(())
+}
+
+fn avx2_available() -> bool {
+    #[cfg(target_arch = "x86_64")]
+    {
+        is_x86_feature_detected!("avx2")
+    }
+    #[cfg(not(target_arch = "x86_64"))]
+    {
+        false
+    }
+}
+
+// AVX2を使う経路。AVX2が使えないCPUで呼び出しても、比較と交換は1要素ずつ行われる
+fn sort_u32_avx2(array: &mut [u32], forward: bool) {
+    do_sort_u32(array, forward, true);
+}
+
+fn sort_u32_scalar(array: &mut [u32], forward: bool) {
+    do_sort_u32(array, forward, false);
+}
+
+// parallel::do_sortと同じ再帰で、avx2がtrueでAVX2が使えるなら比較と交換にAVX2を使う
+fn do_sort_u32(x: &mut [u32], forward: bool, avx2: bool) {
+    if x.len() > 1 {
+        let mid_point = x.len() / 2;
+        let (first, second) = x.split_at_mut(mid_point);
+        if mid_point > parallel_threshold::<u32>() {
+            rayon::join(
+                || do_sort_u32(first, true, avx2),
+                || do_sort_u32(second, false, avx2),
+            );
+        } else {
+            do_sort_u32(first, true, avx2);
+            do_sort_u32(second, false, avx2);
+        }
+        sub_sort_u32(x, forward, avx2);
+    }
+}
+
+fn sub_sort_u32(x: &mut [u32], forward: bool, avx2: bool) {
+    if x.len() > 1 {
+        compare_and_swap_u32(x, forward, avx2);
+        let mid_point = x.len() / 2;
+        let (first, second) = x.split_at_mut(mid_point);
+        if mid_point >= parallel_threshold::<u32>() {
+            rayon::join(
+                || sub_sort_u32(first, forward, avx2),
+                || sub_sort_u32(second, forward, avx2),
+            );
+        } else {
+            sub_sort_u32(first, forward, avx2);
+            sub_sort_u32(second, forward, avx2);
+        }
+    }
+}
+
+// x[i]とx[mid_point + i]を比べ、forwardなら小さい方を前半に、そうでなければ大きい方を前半に置く
+fn compare_and_swap_u32(x: &mut [u32], forward: bool, avx2: bool) {
+    let mid_point = x.len() / 2;
+    let (first, second) = x.split_at_mut(mid_point);
+    #[cfg(target_arch = "x86_64")]
+    {
+        // is_x86_feature_detected!の結果はキャッシュされるので、確認し直すのは安い
+        if avx2 && mid_point >= 8 && is_x86_feature_detected!("avx2") {
+            // 安全性：AVX2が使えることを実行時に確認した
+            unsafe { compare_and_swap_u32_avx2(first, second, forward) };
+            return;
+        }
+    }
+    #[cfg(not(target_arch = "x86_64"))]
+    let _ = avx2;
+    compare_and_swap_u32_scalar(first, second, forward);
+}
+
+fn compare_and_swap_u32_scalar(first: &mut [u32], second: &mut [u32], forward: bool) {
+    for (a, b) in first.iter_mut().zip(second.iter_mut()) {
+        if (*a > *b) == forward {
+            std::mem::swap(a, b);
+        }
+    }
+}
+
+/// # Safety
+///
+/// AVX2に対応したCPUでのみ呼び出せる。firstとsecondは同じ長さでなければならない
+#[cfg(target_arch = "x86_64")]
+#[target_feature(enable = "avx2")]
+unsafe fn compare_and_swap_u32_avx2(first: &mut [u32], second: &mut [u32], forward: bool) {
+    use std::arch::x86_64::*;
+
+    debug_assert_eq!(first.len(), second.len());
+    let lanes = first.len() / 8 * 8;
+    for i in (0..lanes).step_by(8) {
+        // 8要素ずつ読み込み、前半に残す値と後半に移す値を最小値と最大値で求める
+        let a = _mm256_loadu_si256(first.as_ptr().add(i) as *const __m256i);
+        let b = _mm256_loadu_si256(second.as_ptr().add(i) as *const __m256i);
+        let (low, high) = (_mm256_min_epu32(a, b), _mm256_max_epu32(a, b));
+        let (a, b) = if forward { (low, high) } else { (high, low) };
+        _mm256_storeu_si256(first.as_mut_ptr().add(i) as *mut __m256i, a);
+        _mm256_storeu_si256(second.as_mut_ptr().add(i) as *mut __m256i, b);
+    }
+    compare_and_swap_u32_scalar(&mut first[lanes..], &mut second[lanes..], forward);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        avx2_available, pack_keys, pack_keys_scalar, sort_by_u32_key_simd, sort_u32, sort_u32_avx2,
+        sort_u32_scalar,
+    };
+    use crate::parallel::{sort, sort_keys};
+    use crate::utils::new_u32_vec;
+    use crate::SortError;
+    use crate::SortOrder::*;
+
+    #[derive(Debug, Clone, PartialEq)]
+    struct Record {
+        name: String,
+        id: u32,
+        score: f64,
+    }
+
+    fn records(n: usize) -> Vec<Record> {
+        new_u32_vec(n)
+            .into_iter()
+            .enumerate()
+            .map(|(i, v)| Record {
+                name: format!("record-{}", i),
+                // キーが等しい要素が多くなるように値の範囲を狭める
+                id: v % 1000,
+                score: i as f64 / 2.0,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn matches_scalar_cached_key_sort() {
+        for &n in &[1usize, 16, 4096, 1 << 16] {
+            let input = records(n);
+            let mut expected = input.clone();
+            assert_eq!(sort_keys(&mut expected, &|r: &Record| r.id), Ok(()));
+            let mut x = input;
+            sort_by_u32_key_simd(&mut x, &|r: &Record| r.id);
+            assert_eq!(x, expected, "n: {}", n);
+        }
+    }
+
+    #[test]
+    fn non_power_of_two_length() {
+        let mut x = records(1000);
+        sort_by_u32_key_simd(&mut x, &|r: &Record| r.id);
+        assert!(x.windows(2).all(|w| w[0].id <= w[1].id));
+        let mut empty: Vec<Record> = Vec::new();
+        sort_by_u32_key_simd(&mut empty, &|r: &Record| r.id);
+        assert!(empty.is_empty());
+    }
+
+    #[test]
+    fn simd_packing_matches_scalar() {
+        let keys = new_u32_vec(1003);
+        let mut simd = vec![0; keys.len()];
+        let mut scalar = vec![0; keys.len()];
+        pack_keys(&mut simd, &keys, 100);
+        pack_keys_scalar(&mut scalar, &keys, 100);
+        assert_eq!(simd, scalar);
+    }
+
+    #[test]
+    fn sort_u32_matches_parallel_sort() {
+        for &n in &[1usize, 2, 8, 16, 1024, 1 << 16] {
+            for order in &[Ascending, Descending] {
+                let input = new_u32_vec(n);
+                let mut expected = input.clone();
+                assert_eq!(sort(&mut expected, order), Ok(()));
+                let mut x = input;
+                assert_eq!(sort_u32(&mut x, order), Ok(()));
+                assert_eq!(x, expected, "n: {}", n);
+            }
+        }
+        let mut x = vec![3, 1, 2];
+        assert_eq!(
+            sort_u32(&mut x, &Ascending),
+            Err(SortError::not_power_of_two(3))
+        );
+    }
+
+    #[test]
+    fn avx2_and_scalar_paths_agree() {
+        for &n in &[2usize, 16, 64, 1 << 12, 1 << 15] {
+            for &forward in &[true, false] {
+                let input = new_u32_vec(n);
+                let mut scalar = input.clone();
+                sort_u32_scalar(&mut scalar, forward);
+                // AVX2が使えないCPUでは、スカラーの経路どうしを比べることになる
+                let mut simd = input;
+                if avx2_available() {
+                    sort_u32_avx2(&mut simd, forward);
+                } else {
+                    sort_u32_scalar(&mut simd, forward);
+                }
+                assert_eq!(simd, scalar, "n: {}", n);
+            }
+        }
+    }
+}
@@ -0,0 +1,120 @@
+use super::parallel::{apply_permutation, merge_two};
+use super::seq;
+use super::utils::require_power_of_two;
+use super::SortError;
+use std::cmp::Ordering;
+use std::thread;
+
+// rayonを使わずに、標準ライブラリのスレッドだけで並列にソートする
+// 1. 配列をnum_threads個の部分配列（チャンク）に分ける
+// 2. チャンクごとにスレッドを1つ作り（thread::scope）、順次処理のバイトニックソートでソートする
+// 3. ソート済みのチャンクをマージする（呼び出したスレッドで行う）
+// 同時に動くスレッドはnum_threads個までに限られる
+// チャンクもバイトニックソートできるように、要素数が2のべき乗のチャンクに分ける
+// 最も大きいチャンクを半分に分けることを繰り返すので、チャンクの大きさは高々2倍しか違わない
+// num_threadsが0なら1として、要素数より大きければ要素数として扱う
+pub fn sort_by_threads<T, F>(
+    array: &mut [T],
+    comparator: &F,
+    num_threads: usize,
+) -> Result<(), SortError>
+where
+    T: Send + Sync,
+    F: Sync + Fn(&T, &T) -> Ordering,
+{
+    require_power_of_two(array.len())?;
+    let lens = chunk_lens(array.len(), num_threads.clamp(1, array.len()));
+
+    thread::scope(|scope| {
+        let mut rest = &mut *array;
+        for &len in &lens {
+            let (chunk, tail) = rest.split_at_mut(len);
+            rest = tail;
+            scope.spawn(move || seq::sort_by(chunk, comparator));
+        }
+    });
+
+    // 各チャンクの位置の列をマージして、全体の並べ替えの順序を求める
+    let mut runs: Vec<Vec<usize>> = Vec::with_capacity(lens.len());
+    let mut start = 0;
+    for &len in &lens {
+        runs.push((start..start + len).collect());
+        start += len;
+    }
+    let mut permutation = {
+        let source = &*array;
+        let compare = |&i: &usize, &j: &usize| comparator(&source[i], &source[j]);
+        // 隣り合うランを2つずつマージすることを、ランが1つになるまで繰り返す
+        while runs.len() > 1 {
+            let mut merged = Vec::with_capacity(runs.len().div_ceil(2));
+            let mut pairs = runs.into_iter();
+            while let Some(first) = pairs.next() {
+                merged.push(match pairs.next() {
+                    Some(second) => merge_two(first, second, &compare),
+                    None => first,
+                });
+            }
+            runs = merged;
+        }
+        runs.pop().unwrap()
+    };
+    apply_permutation(array, &mut permutation);
+    Ok(())
+}
+
+// 要素数len（2のべき乗）を、2のべき乗のchunks個の大きさに分ける
+// chunksはlen以下でなければならない
+fn chunk_lens(len: usize, chunks: usize) -> Vec<usize> {
+    let mut lens = vec![len];
+    // 先頭が最も大きいので、先頭を半分に分けて末尾に移すと、大きい順に並んだままになる
+    while lens.len() < chunks {
+        let half = lens.remove(0) / 2;
+        lens.extend([half, half]);
+    }
+    lens
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{chunk_lens, sort_by_threads};
+    use crate::parallel::sort_by;
+    use crate::utils::{is_sorted_descending, new_u32_vec};
+    use crate::SortError;
+
+    #[test]
+    fn three_threads() {
+        for &len in &[4usize, 1024, 1 << 16] {
+            let input = new_u32_vec(len);
+            let mut expected = input.clone();
+            assert_eq!(sort_by(&mut expected, &|a, b| a.cmp(b)), Ok(()));
+            let mut x = input;
+            assert_eq!(sort_by_threads(&mut x, &|a, b| a.cmp(b), 3), Ok(()));
+            assert_eq!(x, expected, "len: {}", len);
+        }
+    }
+
+    #[test]
+    fn thread_counts() {
+        let input = new_u32_vec(256);
+        for &threads in &[0, 1, 2, 5, 8, 256, 1000] {
+            let mut x = input.clone();
+            assert_eq!(sort_by_threads(&mut x, &|a, b| b.cmp(a), threads), Ok(()));
+            assert!(is_sorted_descending(&x), "threads: {}", threads);
+        }
+        let mut x = vec![1];
+        assert_eq!(sort_by_threads(&mut x, &|a, b| a.cmp(b), 4), Ok(()));
+        let mut x = vec![3, 2, 1];
+        assert_eq!(
+            sort_by_threads(&mut x, &|a, b| a.cmp(b), 2),
+            Err(SortError::not_power_of_two(3))
+        );
+    }
+
+    #[test]
+    fn chunk_lens_are_powers_of_two() {
+        assert_eq!(chunk_lens(1024, 1), vec![1024]);
+        assert_eq!(chunk_lens(1024, 3), vec![512, 256, 256]);
+        assert_eq!(chunk_lens(1024, 4), vec![256, 256, 256, 256]);
+        assert_eq!(chunk_lens(8, 8), vec![1; 8]);
+    }
+}
@@ -1,6 +1,8 @@
+use super::SortError;
 use rand::{Rng, SeedableRng};
 use rand::distributions::Standard;
 use rand_pcg::Pcg64Mcg;
+use std::time::Duration;
 
 pub fn new_u32_vec(n: usize) -> Vec<u32> {
     // RNGを初期化する。再現性を持たせるため毎回同じシード値を使う
@@ -28,4 +30,152 @@ pub fn is_sorted_ascending<T: Ord>(x: &[T]) -> bool {
 
 pub fn is_sorted_descending<T: Ord>(x: &[T]) -> bool {
     x.windows(2).all(|pair| pair[0] >= pair[1])
-}
\ No newline at end of file
+}
+
+// aとbが同じ要素を同じ個数ずつ含む（一方が他方を並べ替えたものになっている）ならtrueを返す
+// ソートの結果が入力の並べ替えになっているかをテストで確かめるときに使う
+// 両方の複製を標準ライブラリのソートで並べてから比較する
+pub fn is_permutation_of<T: Ord + Clone>(a: &[T], b: &[T]) -> bool {
+    if a.len() != b.len() {
+        return false;
+    }
+    let mut a = a.to_vec();
+    let mut b = b.to_vec();
+    a.sort_unstable();
+    b.sort_unstable();
+    a == b
+}
+// Nバイトの大きさを持つ、ベンチマーク用の構造体
+// 配列の辞書順で大小を比較する
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Payload<const N: usize> {
+    pub bytes: [u8; N],
+}
+
+pub fn new_payload_vec<const N: usize>(n: usize) -> Vec<Payload<N>> {
+    let mut rng = Pcg64Mcg::from_seed([0; 16]);
+    (0..n)
+        .map(|_| {
+            let mut bytes = [0; N];
+            rng.fill(&mut bytes[..]);
+            Payload { bytes }
+        })
+        .collect()
+}
+
+// テスト用に、要素数lenのランダムなu32の入力と、それを昇順に並べた期待値を組で返す
+// 期待値は標準ライブラリのsort_unstableで求めるので、テストではソートの結果を
+// そのまま期待値と比べればよい。同じseedからは常に同じ入力を作る
+pub fn new_test_case(len: usize, seed: u64) -> (Vec<u32>, Vec<u32>) {
+    let mut rng = Pcg64Mcg::seed_from_u64(seed);
+    let input: Vec<u32> = rng.sample_iter(&Standard).take(len).collect();
+    let mut expected = input.clone();
+    expected.sort_unstable();
+    (input, expected)
+}
+
+// 0以上1日未満のDuration（ナノ秒単位）をn個作る。イベントの時刻でソートするベンチマーク用
+pub fn new_duration_vec(n: usize) -> Vec<Duration> {
+    let mut rng = Pcg64Mcg::from_seed([0; 16]);
+    (0..n)
+        .map(|_| Duration::from_nanos(rng.gen_range(0, NANOS_PER_DAY)))
+        .collect()
+}
+
+const NANOS_PER_DAY: u64 = 24 * 60 * 60 * 1_000_000_000;
+
+// 要素数lenが2のべき乗ならOk(k)（len == 2^k）を返し、そうでなければエラーを返す
+// kはバイトニックソートの段数になるので、ソートの前に段数を知りたいときにも使える
+pub fn require_power_of_two(len: usize) -> Result<u32, SortError> {
+    log2_exact(len).ok_or_else(|| SortError::not_power_of_two(len))
+}
+
+// nが2のべき乗（n == 1 << k）ならSome(k)を、そうでなければNoneを返す
+pub fn log2_exact(n: usize) -> Option<u32> {
+    if n.is_power_of_two() {
+        Some(n.trailing_zeros())
+    } else {
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        is_permutation_of, is_sorted_ascending, log2_exact, new_test_case, require_power_of_two,
+    };
+    use crate::SortError;
+
+    #[test]
+    fn require_power_of_two_exponents() {
+        assert_eq!(require_power_of_two(1), Ok(0));
+        assert_eq!(require_power_of_two(2), Ok(1));
+        assert_eq!(require_power_of_two(1024), Ok(10));
+        assert_eq!(require_power_of_two(1 << 31), Ok(31));
+    }
+
+    #[test]
+    fn require_power_of_two_errors() {
+        assert_eq!(
+            require_power_of_two(0),
+            Err(SortError::not_power_of_two(0))
+        );
+        assert_eq!(
+            require_power_of_two(3),
+            Err(SortError::not_power_of_two(3))
+        );
+        assert_eq!(
+            require_power_of_two(1000),
+            Err(SortError::not_power_of_two(1000))
+        );
+    }
+
+    #[test]
+    fn log2_exact_powers() {
+        assert_eq!(log2_exact(1), Some(0));
+        assert_eq!(log2_exact(2), Some(1));
+        assert_eq!(log2_exact(1024), Some(10));
+    }
+
+    #[test]
+    fn log2_exact_non_powers() {
+        assert_eq!(log2_exact(0), None);
+        assert_eq!(log2_exact(3), None);
+        assert_eq!(log2_exact(1023), None);
+        assert_eq!(log2_exact(1025), None);
+    }
+
+    #[test]
+    fn is_permutation_of_same_multiset() {
+        assert!(is_permutation_of(&[3, 1, 2, 1], &[1, 1, 2, 3]));
+        assert!(is_permutation_of(&["b", "a"], &["a", "b"]));
+        assert!(is_permutation_of::<u32>(&[], &[]));
+    }
+
+    #[test]
+    fn is_permutation_of_different_lengths() {
+        assert!(!is_permutation_of(&[1, 2, 3], &[1, 2]));
+        assert!(!is_permutation_of(&[], &[1]));
+    }
+
+    #[test]
+    fn is_permutation_of_different_contents() {
+        assert!(!is_permutation_of(&[1, 2, 3], &[1, 2, 4]));
+        // 同じ値を含んでいても、個数が異なれば並べ替えではない
+        assert!(!is_permutation_of(&[1, 1, 2], &[1, 2, 2]));
+    }
+
+    #[test]
+    fn new_test_case_expected_is_sorted_input() {
+        for &(len, seed) in &[(0, 0), (1, 1), (16, 2), (1000, 3), (1 << 12, 4)] {
+            let (input, expected) = new_test_case(len, seed);
+            assert_eq!(input.len(), len);
+            assert!(is_sorted_ascending(&expected));
+            assert!(is_permutation_of(&input, &expected));
+        }
+
+        // 同じシードからは同じ入力が、異なるシードからは異なる入力が作られる
+        assert_eq!(new_test_case(64, 7), new_test_case(64, 7));
+        assert_ne!(new_test_case(64, 7).0, new_test_case(64, 8).0);
+    }
+}
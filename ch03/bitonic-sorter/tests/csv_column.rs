@@ -0,0 +1,101 @@
+#![cfg(feature = "csv")]
+
+use bitonic_sorter::csv::sort_csv_column;
+use bitonic_sorter::SortOrder::*;
+
+use std::fs;
+use std::io;
+use std::path::PathBuf;
+
+// CSVファイルを列の値でソートするテスト
+// `cargo test --features csv` で実行できる
+
+const INPUT: &str = "\
+name,age,city
+Taro,16,Tokyo
+Hanako,14,Osaka
+Kyoko,15,Kyoto
+Ryosuke,17,Nagoya
+Jiro,9,Sapporo
+";
+
+// テストごとに別のディレクトリを作り、入力のファイルを置く
+fn test_dir(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("bitonic-csv-{}-{}", std::process::id(), name));
+    fs::create_dir_all(&dir).unwrap();
+    fs::write(dir.join("input.csv"), INPUT).unwrap();
+    dir
+}
+
+#[test]
+fn sort_by_numeric_column() {
+    let dir = test_dir("numeric");
+    let output = dir.join("output.csv");
+    sort_csv_column(&dir.join("input.csv"), &output, 1, &Ascending, true).unwrap();
+    // 数として比較するので、9は14より前に来る
+    assert_eq!(
+        fs::read_to_string(&output).unwrap(),
+        "\
+name,age,city
+Jiro,9,Sapporo
+Hanako,14,Osaka
+Kyoko,15,Kyoto
+Taro,16,Tokyo
+Ryosuke,17,Nagoya
+"
+    );
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn sort_by_string_column() {
+    let dir = test_dir("string");
+    let output = dir.join("output.csv");
+    sort_csv_column(&dir.join("input.csv"), &output, 2, &Descending, false).unwrap();
+    assert_eq!(
+        fs::read_to_string(&output).unwrap(),
+        "\
+name,age,city
+Taro,16,Tokyo
+Jiro,9,Sapporo
+Hanako,14,Osaka
+Ryosuke,17,Nagoya
+Kyoko,15,Kyoto
+"
+    );
+
+    // 文字列として比較すると、"9"は"17"より後ろに来る
+    sort_csv_column(&dir.join("input.csv"), &output, 1, &Ascending, false).unwrap();
+    let ages: Vec<String> = fs::read_to_string(&output)
+        .unwrap()
+        .lines()
+        .skip(1)
+        .map(|line| line.split(',').nth(1).unwrap().to_string())
+        .collect();
+    assert_eq!(ages, vec!["14", "15", "16", "17", "9"]);
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn invalid_numbers_are_errors() {
+    let dir = test_dir("invalid");
+    let err = sort_csv_column(
+        &dir.join("input.csv"),
+        &dir.join("output.csv"),
+        0,
+        &Ascending,
+        true,
+    )
+    .unwrap_err();
+    assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+    let err = sort_csv_column(
+        &dir.join("input.csv"),
+        &dir.join("output.csv"),
+        5,
+        &Ascending,
+        false,
+    )
+    .unwrap_err();
+    assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+    fs::remove_dir_all(dir).unwrap();
+}
@@ -0,0 +1,23 @@
+use bitonic_sorter::parallel::{parallel_threshold, sort_by_counting};
+use bitonic_sorter::utils::{is_sorted_ascending, new_u32_vec};
+
+// しきい値は最初に使われたときに一度だけ環境変数から読まれるので、
+// 環境変数を設定するテストは、独立したプロセスになるこのファイルに1つだけ置く
+#[test]
+fn threshold_from_environment_variable() {
+    std::env::set_var("BITONIC_THRESHOLD", "1048576");
+    assert_eq!(parallel_threshold::<u32>(), 1 << 20);
+    // 要素が大きいときは、環境変数の値を基準に要素のサイズに応じて下げる
+    assert_eq!(parallel_threshold::<[u64; 4]>(), 1 << 18);
+
+    // 既定のしきい値（4096）なら2^14要素のソートは並列に処理されるが、
+    // しきい値が要素数より大きいので、一度も並列に処理しない
+    let mut x = new_u32_vec(1 << 14);
+    let metrics = sort_by_counting(&mut x, &|a, b| a.cmp(b)).unwrap();
+    assert!(is_sorted_ascending(&x));
+    assert_eq!(metrics.parallel_tasks, 0);
+
+    // 一度読んだ後で環境変数を変えても、しきい値は変わらない
+    std::env::set_var("BITONIC_THRESHOLD", "256");
+    assert_eq!(parallel_threshold::<u32>(), 1 << 20);
+}
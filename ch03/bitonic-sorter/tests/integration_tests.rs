@@ -26,3 +26,49 @@ speed up: \d+\.\d+x
     assert!(re.is_match(output.stdout_str()));
     assert!(output.stderr_str().is_empty());
 }
+
+#[test]
+fn sort_u32_text() {
+    let testdir = TestDir::new("./examples/sort_u32", "Sort a text file");
+    testdir.create_file("input.txt", "30\n10\n20\n");
+    testdir
+        .cmd()
+        .arg("input.txt")
+        .arg("output.txt")
+        .expect_success();
+    testdir.expect_file_contents("output.txt", "10\n20\n30\n");
+}
+
+#[test]
+fn sort_u32_binary() {
+    use bitonic_sorter::io::{read_u32_be, read_u32_le, write_u32_be, write_u32_le};
+    use std::fs::File;
+
+    let input = vec![7, 0x0102_0304, 1, 5, 3];
+    let testdir = TestDir::new("./examples/sort_u32", "Sort binary files");
+    write_u32_le(File::create(testdir.path("input.le")).unwrap(), &input).unwrap();
+    write_u32_be(File::create(testdir.path("input.be")).unwrap(), &input).unwrap();
+    testdir
+        .cmd()
+        .args(["--format", "binary-le", "input.le", "output.le"])
+        .expect_success();
+    testdir
+        .cmd()
+        .args(["--format", "binary-be", "--descending", "input.be", "output.be"])
+        .expect_success();
+
+    let le = read_u32_le(File::open(testdir.path("output.le")).unwrap()).unwrap();
+    assert_eq!(le, vec![1, 3, 5, 7, 0x0102_0304]);
+    let be = read_u32_be(File::open(testdir.path("output.be")).unwrap()).unwrap();
+    assert_eq!(be, vec![0x0102_0304, 7, 5, 3, 1]);
+}
+
+#[test]
+fn sort_u32_unknown_format() {
+    let testdir = TestDir::new("./examples/sort_u32", "Reject an unknown format");
+    let output = testdir
+        .cmd()
+        .args(["--format", "csv", "input", "output"])
+        .expect_failure();
+    assert!(output.stderr_str().contains("unknown format: csv"));
+}
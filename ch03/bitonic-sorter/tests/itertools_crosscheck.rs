@@ -0,0 +1,63 @@
+use bitonic_sorter::parallel::sort;
+use bitonic_sorter::SortOrder::*;
+
+use itertools::Itertools;
+use rand::{Rng, SeedableRng};
+use rand_pcg::Pcg64Mcg;
+
+// parallel::sortの結果を、標準ライブラリ以外で広く使われているitertoolsのsortedと比べる
+// 要素数は2のべき乗に限る
+
+const LENGTHS: [usize; 6] = [1, 2, 16, 1024, 4096, 1 << 15];
+
+#[test]
+fn same_as_itertools_for_u32() {
+    let mut rng = Pcg64Mcg::from_seed([11; 16]);
+    for &len in &LENGTHS {
+        let input: Vec<u32> = (0..len).map(|_| rng.gen()).collect();
+        let mut x = input.clone();
+        assert!(sort(&mut x, &Ascending).is_ok());
+        assert_eq!(x, input.iter().copied().sorted().collect::<Vec<_>>());
+
+        let mut x = input.clone();
+        assert!(sort(&mut x, &Descending).is_ok());
+        assert_eq!(x, input.into_iter().sorted().rev().collect::<Vec<_>>());
+    }
+}
+
+#[test]
+fn same_as_itertools_for_i64() {
+    let mut rng = Pcg64Mcg::from_seed([12; 16]);
+    for &len in &LENGTHS {
+        // 重複が多くなるように値の範囲を狭める
+        let input: Vec<i64> = (0..len).map(|_| rng.gen_range(-100, 100)).collect();
+        let mut x = input.clone();
+        assert!(sort(&mut x, &Ascending).is_ok());
+        assert_eq!(x, input.iter().copied().sorted().collect::<Vec<_>>());
+
+        let mut x = input.clone();
+        assert!(sort(&mut x, &Descending).is_ok());
+        assert_eq!(x, input.into_iter().sorted().rev().collect::<Vec<_>>());
+    }
+}
+
+#[test]
+fn same_as_itertools_for_str() {
+    let mut rng = Pcg64Mcg::from_seed([13; 16]);
+    for &len in &LENGTHS {
+        let words: Vec<String> = (0..len)
+            .map(|_| {
+                let n = rng.gen_range(0, 8);
+                (0..n).map(|_| rng.gen_range(b'a', b'e') as char).collect()
+            })
+            .collect();
+        let input: Vec<&str> = words.iter().map(|w| w.as_str()).collect();
+        let mut x = input.clone();
+        assert!(sort(&mut x, &Ascending).is_ok());
+        assert_eq!(x, input.iter().copied().sorted().collect::<Vec<_>>());
+
+        let mut x = input.clone();
+        assert!(sort(&mut x, &Descending).is_ok());
+        assert_eq!(x, input.into_iter().sorted().rev().collect::<Vec<_>>());
+    }
+}
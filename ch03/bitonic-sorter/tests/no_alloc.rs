@@ -0,0 +1,81 @@
+use bitonic_sorter::parallel::{
+    parallel_threshold, partition_by, sort, sort_by, sort_by_reporting,
+};
+use bitonic_sorter::utils::new_u32_vec;
+use bitonic_sorter::SortOrder::*;
+
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::cell::Cell;
+
+// メモリの確保を数えるアロケータ
+// テストは並行に実行されるので、スレッドごとに数える
+struct CountingAllocator;
+
+thread_local! {
+    static ALLOCATIONS: Cell<usize> = const { Cell::new(0) };
+}
+
+unsafe impl GlobalAlloc for CountingAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        ALLOCATIONS.with(|count| count.set(count.get() + 1));
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout)
+    }
+}
+
+#[global_allocator]
+static ALLOCATOR: CountingAllocator = CountingAllocator;
+
+// クロージャfを実行する間に、このスレッドでメモリを確保した回数を返す
+fn count_allocations<F: FnOnce()>(f: F) -> usize {
+    // しきい値は最初の呼び出しで環境変数から読むので（そのときだけメモリを確保する）、
+    // 数える前に読ませておく
+    parallel_threshold::<u32>();
+    let before = ALLOCATIONS.with(|count| count.get());
+    f();
+    ALLOCATIONS.with(|count| count.get()) - before
+}
+
+#[test]
+fn sort_does_not_allocate() {
+    // データは事前に用意しておく
+    let mut x = new_u32_vec(4096);
+    let allocations = count_allocations(|| {
+        assert!(sort(&mut x, &Ascending).is_ok());
+        assert!(sort(&mut x, &Descending).is_ok());
+    });
+    assert_eq!(allocations, 0);
+}
+
+#[test]
+fn sort_by_does_not_allocate() {
+    let mut x = new_u32_vec(2048);
+    let allocations = count_allocations(|| {
+        assert!(sort_by(&mut x, &|a, b| b.cmp(a)).is_ok());
+        assert!(sort_by_reporting(&mut x, &|a, b| a.cmp(b)).is_ok());
+        // 要素数が2のべき乗でないときのエラーもメモリを確保しない
+        assert!(sort_by(&mut x[..3], &|a, b| a.cmp(b)).is_err());
+    });
+    assert_eq!(allocations, 0);
+}
+
+#[test]
+fn partition_by_does_not_allocate() {
+    let mut x = new_u32_vec(1000);
+    let allocations = count_allocations(|| {
+        partition_by(&mut x, &|v| v % 2 == 0);
+    });
+    assert_eq!(allocations, 0);
+}
+
+#[test]
+fn counting_allocator_detects_allocation() {
+    // アロケータが確保を正しく数えていることを確認する
+    let allocations = count_allocations(|| {
+        std::hint::black_box(vec![0u32; 16]);
+    });
+    assert_eq!(allocations, 1);
+}